@@ -1,15 +1,34 @@
 use std::borrow::Cow;
+use std::collections::VecDeque;
 use std::ops::Range;
 
+use unicode_segmentation::UnicodeSegmentation;
 use vte::Parser;
 use vte::Perform;
 
+use crate::Align;
+use crate::WidthProfile;
+use crate::sgr::SgrState;
+
+pub use crate::elide::elide_path;
+
+pub(crate) const ELLIPSIS: &str = "…";
+
 pub struct AnsiToken {
   pub range: Range<usize>,
   pub is_escape: bool,
 }
 
 pub fn strip_ansi_codes(text: &str) -> Cow<'_, str> {
+  strip_ansi_codes_with_profile(text, WidthProfile::default())
+}
+
+/// Strips the escape sequences from the text, where horizontal movements
+/// become the spaces they move over as measured by the width profile.
+pub fn strip_ansi_codes_with_profile(
+  text: &str,
+  width_profile: WidthProfile,
+) -> Cow<'_, str> {
   // fast path: no escape byte means no parser work and no allocation
   if !text.as_bytes().contains(&0x1b) {
     return Cow::Borrowed(text);
@@ -19,47 +38,896 @@ pub fn strip_ansi_codes(text: &str) -> Cow<'_, str> {
     Cow::Borrowed(text)
   } else {
     let mut final_text = String::new();
+    let mut column = 0;
     for token in tokens {
       let token_text = &text[token.range];
       if !token.is_escape {
         final_text.push_str(token_text);
-      } else if token_text == "\u{1b}[1C" {
-        // change a single move cursor right to a space
-        final_text.push(' ');
+        column = match token_text.rfind('\n') {
+          Some(index) => width_profile.str_width(&token_text[index + 1..]),
+          None => column + width_profile.str_width(token_text),
+        };
+      } else {
+        // horizontal movements become the spaces they move over
+        let width = movement_width(token_text, column);
+        final_text.extend(std::iter::repeat_n(' ', width));
+        column += width;
       }
     }
     Cow::Owned(final_text)
   }
 }
 
-/// Tokenizes the provided text into ansi escape sequences
-pub fn tokenize(text: &str) -> Vec<AnsiToken> {
-  let mut parser = Parser::new();
-  let mut performer = Performer {
-    current_end_index: 0,
-    last_handled_end_index: 0,
-    last_handled_start_index: 0,
-    tokens: Vec::new(),
-    is_current_escape: false,
+/// Gets the number of cells that a horizontal movement escape sequence
+/// moves over when the cursor is at the provided column, which is zero
+/// for other escape sequences.
+///
+/// This is the cursor forward (ex. `ESC[5C`) and absolute column (ex.
+/// `ESC[20G`) sequences, which tools use instead of writing spaces. Erase
+/// character (ex. `ESC[3X`) leaves the cursor where it is.
+pub(crate) fn movement_width(escape: &str, column: usize) -> usize {
+  let Some(rest) = escape
+    .strip_prefix("\x1b[")
+    .or_else(|| escape.strip_prefix('\u{9b}'))
+  else {
+    return 0;
+  };
+  let Some(action) = rest.chars().last() else {
+    return 0;
+  };
+  let params = &rest[..rest.len() - action.len_utf8()];
+  if !params.bytes().all(|b| b.is_ascii_digit()) {
+    return 0;
+  }
+  // a missing or zero count is treated as one
+  let count = params.parse::<u16>().unwrap_or(0).max(1) as usize;
+  match action {
+    'C' | 'a' => count,
+    'G' | '`' => (count - 1).saturating_sub(column),
+    _ => 0,
+  }
+}
+
+/// Takes the columns in the range out of text that may contain ANSI
+/// escape sequences (ex. `slice_columns(text, 10..40, WidthProfile::Narrow)`).
+///
+/// Escape sequences before the range are kept so the slice has the same
+/// styles and hyperlinks, and any that are left open at the end of the
+/// slice are closed. A wide char that's only partially in the range is
+/// replaced with spaces. Columns are measured using the width profile.
+pub fn slice_columns(
+  text: &str,
+  columns: Range<usize>,
+  profile: WidthProfile,
+) -> Cow<'_, str> {
+  if columns.start == 0 && profile.text_width(text) <= columns.end {
+    return close_open_state(text);
+  }
+  Cow::Owned(slice(text, columns, profile))
+}
+
+/// Truncates text that may contain ANSI escape sequences to the width as
+/// measured by the profile, ending it with an ellipsis when it doesn't fit.
+///
+/// Any styles or hyperlinks that are left open are closed after the
+/// ellipsis.
+pub fn truncate_to_width(
+  text: &str,
+  max_width: usize,
+  profile: WidthProfile,
+) -> Cow<'_, str> {
+  if profile.text_width(text) <= max_width {
+    return close_open_state(text);
+  }
+  // when even the ellipsis doesn't fit, only the escape sequences are kept
+  let ellipsis_width = profile.str_width(ELLIPSIS);
+  let (budget, ellipsis) = match max_width.checked_sub(ellipsis_width) {
+    Some(budget) => (budget, ELLIPSIS),
+    None => (0, ""),
+  };
+  let mut final_text = String::with_capacity(text.len() + ELLIPSIS.len());
+  let mut sgr_state = SgrState::default();
+  let mut is_hyperlink_open = false;
+  let mut column = 0;
+  let mut is_truncated = false;
+  for token in tokenize(text) {
+    let chunk = &text[token.range];
+    let width = if token.is_escape {
+      movement_width(chunk, column)
+    } else {
+      0
+    };
+    if token.is_escape && width == 0 {
+      // escape sequences in the removed text are kept so that styles
+      // closed there still apply after the ellipsis
+      sgr_state.apply_escape(chunk);
+      if let Some(is_open) = hyperlink_state(chunk) {
+        is_hyperlink_open = is_open;
+      }
+      final_text.push_str(chunk);
+      continue;
+    }
+    if is_truncated {
+      continue;
+    }
+    let cells: Vec<(&str, usize)> = if token.is_escape {
+      vec![(chunk, width)]
+    } else {
+      chunk
+        .graphemes(true)
+        .map(|grapheme| (grapheme, profile.grapheme_width(grapheme)))
+        .collect()
+    };
+    for (cell, width) in cells {
+      if column + width > budget {
+        final_text.push_str(ellipsis);
+        is_truncated = true;
+        break;
+      }
+      final_text.push_str(cell);
+      column += width;
+    }
+  }
+  push_closing_escapes(&mut final_text, &sgr_state, is_hyperlink_open);
+  Cow::Owned(final_text)
+}
+
+// Closes any style or hyperlink that the text leaves open, which borrows
+// the text when there's nothing to close.
+fn close_open_state(text: &str) -> Cow<'_, str> {
+  let mut sgr_state = SgrState::default();
+  let mut is_hyperlink_open = false;
+  for token in tokenize(text) {
+    if token.is_escape {
+      let escape = &text[token.range];
+      sgr_state.apply_escape(escape);
+      if let Some(is_open) = hyperlink_state(escape) {
+        is_hyperlink_open = is_open;
+      }
+    }
+  }
+  if sgr_state.is_default() && !is_hyperlink_open {
+    return Cow::Borrowed(text);
+  }
+  let mut final_text = text.to_string();
+  push_closing_escapes(&mut final_text, &sgr_state, is_hyperlink_open);
+  Cow::Owned(final_text)
+}
+
+fn push_closing_escapes(
+  final_text: &mut String,
+  sgr_state: &SgrState,
+  is_hyperlink_open: bool,
+) {
+  if !sgr_state.is_default() {
+    final_text.push_str("\x1b[0m");
+  }
+  if is_hyperlink_open {
+    final_text.push_str("\x1b]8;;\x1b\\");
+  }
+}
+
+/// Pads text that may contain ANSI escape sequences with spaces so it
+/// occupies at least the width, where the alignment says which side of
+/// the text the spaces go. The text is measured using the width profile.
+///
+/// Any styles or hyperlinks that are left open are closed before the
+/// padding.
+pub fn pad_to_width(
+  text: &str,
+  width: usize,
+  align: Align,
+  profile: WidthProfile,
+) -> Cow<'_, str> {
+  let text_width = profile.text_width(text);
+  let text = close_open_state(text);
+  let Some(free_width) = width.checked_sub(text_width).filter(|w| *w > 0)
+  else {
+    return text;
+  };
+  let start_width = match align {
+    Align::Left => 0,
+    Align::Right => free_width,
+    Align::Center => free_width / 2,
   };
-  for byte in text.as_bytes() {
-    performer.current_end_index += 1;
-    parser.advance(&mut performer, *byte);
+  let mut final_text = String::with_capacity(text.len() + free_width);
+  final_text.extend(std::iter::repeat_n(' ', start_width));
+  final_text.push_str(&text);
+  final_text.extend(std::iter::repeat_n(' ', free_width - start_width));
+  Cow::Owned(final_text)
+}
+
+fn slice(text: &str, columns: Range<usize>, profile: WidthProfile) -> String {
+  let mut final_text = String::with_capacity(text.len());
+  let mut sgr_state = SgrState::default();
+  let mut is_hyperlink_open = false;
+  let mut column = 0;
+  for token in tokenize(text) {
+    if column >= columns.end {
+      break;
+    }
+    let chunk = &text[token.range];
+    if !token.is_escape {
+      for grapheme in chunk.graphemes(true) {
+        let width = profile.grapheme_width(grapheme);
+        push_cell(&mut final_text, grapheme, width, column, &columns);
+        column += width;
+      }
+      continue;
+    }
+    let width = movement_width(chunk, column);
+    if width > 0 {
+      push_cell(&mut final_text, chunk, width, column, &columns);
+      column += width;
+    } else {
+      sgr_state.apply_escape(chunk);
+      if let Some(is_open) = hyperlink_state(chunk) {
+        is_hyperlink_open = is_open;
+      }
+      final_text.push_str(chunk);
+    }
+  }
+  push_closing_escapes(&mut final_text, &sgr_state, is_hyperlink_open);
+  final_text
+}
+
+// Writes the part of a cell that's in the columns, where a partial cell
+// is written as spaces.
+fn push_cell(
+  final_text: &mut String,
+  cell: &str,
+  width: usize,
+  column: usize,
+  columns: &Range<usize>,
+) {
+  let start = column.max(columns.start);
+  let end = (column + width).min(columns.end);
+  if start == column && end == column + width {
+    final_text.push_str(cell);
+  } else if start < end {
+    final_text.extend(std::iter::repeat_n(' ', end - start));
+  }
+}
+
+/// Gets whether an OSC 8 escape sequence opens a hyperlink (`true`) or
+/// closes it (`false`), which is `None` for other escape sequences.
+fn hyperlink_state(escape: &str) -> Option<bool> {
+  let rest = escape
+    .strip_prefix("\x1b]8;")
+    .or_else(|| escape.strip_prefix("\u{9d}8;"))?;
+  let (_params, url) = rest.split_once(';')?;
+  let url = url.trim_end_matches(['\x07', '\x1b', '\\', '\u{9c}']);
+  Some(!url.is_empty())
+}
+
+/// Removes the escape sequences that style text, which are SGR sequences
+/// and OSC 8 hyperlinks.
+pub fn strip_styles(text: &str) -> Cow<'_, str> {
+  if !text.contains(['\x1b', '\u{9b}', '\u{9d}']) {
+    return Cow::Borrowed(text);
+  }
+  let mut final_text = String::with_capacity(text.len());
+  for token in tokenize(text) {
+    let token_text = &text[token.range];
+    if !token.is_escape || !is_style_escape(token_text) {
+      final_text.push_str(token_text);
+    }
+  }
+  Cow::Owned(final_text)
+}
+
+/// How `sanitize` handles escape sequences and control characters that
+/// aren't allowed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SanitizeMode {
+  /// Removes escape sequences and displays control characters using
+  /// caret notation (ex. `^G`).
+  #[default]
+  Remove,
+  /// Displays escape sequences and control characters using caret
+  /// notation (ex. `^[[2J`).
+  Show,
+}
+
+/// Makes untrusted text safe to render by only keeping the escape
+/// sequences that don't move the cursor or change the terminal's state,
+/// which are SGR sequences (colors and text attributes) and OSC 8
+/// hyperlinks. A `\r` that isn't followed by `\n` is also disallowed,
+/// since it moves the cursor back over the line.
+pub fn sanitize(text: &str, mode: SanitizeMode) -> Cow<'_, str> {
+  // fast path: no control characters means nothing to sanitize
+  if !text
+    .char_indices()
+    .any(|(index, c)| is_disallowed_control_at(text, index, c))
+  {
+    return Cow::Borrowed(text);
+  }
+  let text = to_seven_bit_controls(text);
+  let mut final_text = String::with_capacity(text.len());
+  let mut is_hyperlink_open = false;
+  for token in tokenize(&text) {
+    let token_text = &text[token.range];
+    if !token.is_escape {
+      push_caret_notation(token_text, &mut final_text);
+    } else if is_complete_style_escape(token_text) {
+      if let Some(is_open) = hyperlink_state(token_text) {
+        is_hyperlink_open = is_open;
+      }
+      final_text.push_str(token_text);
+    } else if mode == SanitizeMode::Show || token_text.chars().nth(1).is_none()
+    {
+      // lone control characters are always displayed
+      push_caret_notation(token_text, &mut final_text);
+    }
+  }
+  if is_hyperlink_open {
+    final_text.push_str("\x1b]8;;\x1b\\");
+  }
+  Cow::Owned(final_text)
+}
+
+// Gets whether the 7-bit escape sequence is an SGR sequence or an OSC 8
+// hyperlink that ends in BEL or ST, since an unterminated string would
+// swallow the text after it.
+fn is_complete_style_escape(escape: &str) -> bool {
+  crate::sgr::sgr_params(escape).is_some()
+    || escape.starts_with("\x1b]8;")
+      && (escape.ends_with('\x07') || escape.ends_with("\x1b\\"))
+}
+
+fn is_style_escape(escape: &str) -> bool {
+  crate::sgr::sgr_params(escape).is_some()
+    || escape.starts_with("\x1b]8;")
+    || escape.starts_with("\u{9d}8;")
+}
+
+// Replaces the 8-bit C1 controls (ex. `\u{9b}` for CSI) with their 7-bit
+// ESC equivalents, so that both forms are handled the same.
+fn to_seven_bit_controls(text: &str) -> Cow<'_, str> {
+  let is_c1_control = |c: char| ('\u{80}'..='\u{9f}').contains(&c);
+  if !text.contains(is_c1_control) {
+    return Cow::Borrowed(text);
+  }
+  let mut output = String::with_capacity(text.len());
+  for c in text.chars() {
+    if is_c1_control(c) {
+      output.push('\x1b');
+      output.push(char::from((c as u32 - 0x40) as u8));
+    } else {
+      output.push(c);
+    }
+  }
+  Cow::Owned(output)
+}
+
+fn is_disallowed_control_at(text: &str, index: usize, c: char) -> bool {
+  match c {
+    '\n' | '\t' => false,
+    '\r' => !text[index + 1..].starts_with('\n'),
+    _ => c.is_control(),
   }
+}
+
+// Writes the text with control characters in caret notation (ex. `^[`
+// for ESC), where C1 controls are written as their ESC equivalent.
+fn push_caret_notation(text: &str, output: &mut String) {
+  for (index, c) in text.char_indices() {
+    match c as u32 {
+      _ if !is_disallowed_control_at(text, index, c) => output.push(c),
+      code @ 0..0x20 => {
+        output.push('^');
+        output.push(char::from(code as u8 + 0x40));
+      }
+      0x7f => output.push_str("^?"),
+      code => {
+        output.push_str("^[");
+        output.push(char::from((code - 0x40) as u8));
+      }
+    }
+  }
+}
+
+/// Tokenizes the provided text into ansi escape sequences
+pub fn tokenize(text: &str) -> Vec<AnsiToken> {
+  let mut performer = Performer::new(false);
+  performer.advance(text.as_bytes());
   performer.mark_end();
   performer.tokens
 }
 
+/// A token of text along with what kind of escape sequence it is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedToken {
+  pub range: Range<usize>,
+  pub kind: TokenKind,
+}
+
+/// The kind of a token from `parse_tokens`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+  /// Text to display, which may contain newlines, carriage returns,
+  /// and tabs.
+  Text,
+  /// A C0 control character other than a newline, carriage return, or
+  /// tab (ex. BEL or backspace).
+  Control(u8),
+  /// A Select Graphic Rendition sequence (ex. `ESC[1;31m`).
+  Sgr(Vec<SgrAttribute>),
+  /// A Control Sequence Introducer sequence other than SGR
+  /// (ex. `ESC[2K`), where each parameter holds its `:` separated
+  /// sub-parameters.
+  Csi {
+    params: Vec<Vec<u16>>,
+    intermediates: Vec<u8>,
+    action: char,
+  },
+  /// An Operating System Command (ex. `ESC]8;;https://example.com ESC\`)
+  /// with its `;` separated parameters.
+  Osc(Vec<Vec<u8>>),
+  /// A Device Control String with the data between its header and
+  /// terminator.
+  Dcs {
+    params: Vec<Vec<u16>>,
+    intermediates: Vec<u8>,
+    action: char,
+    data: Vec<u8>,
+  },
+  /// Any other escape sequence (ex. `ESC 7`).
+  Esc { intermediates: Vec<u8>, byte: u8 },
+  /// An escape sequence that's cut off at the end of the text.
+  Incomplete,
+}
+
+/// An attribute set by an SGR sequence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SgrAttribute {
+  Reset,
+  Bold,
+  Dim,
+  Italic,
+  /// An underline with its style (1 for single, 2 for double, 3 for
+  /// curly, 4 for dotted, and 5 for dashed).
+  Underline(u16),
+  Blink,
+  Inverse,
+  Hidden,
+  Strikethrough,
+  Overline,
+  /// Neither bold nor dim.
+  NormalIntensity,
+  NotItalic,
+  NotUnderlined,
+  NotBlinking,
+  NotInverse,
+  NotHidden,
+  NotStrikethrough,
+  NotOverlined,
+  Foreground(Color),
+  Background(Color),
+  UnderlineColor(Color),
+  DefaultForeground,
+  DefaultBackground,
+  DefaultUnderlineColor,
+  /// A parameter that isn't recognized, with its sub-parameters.
+  Unknown(Vec<u16>),
+}
+
+/// A color set by an SGR sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+  /// One of the 16 standard colors (0-7) and their bright variants (8-15).
+  Ansi(u8),
+  /// A color of the 256 color palette.
+  Indexed(u8),
+  Rgb(u8, u8, u8),
+}
+
+/// Tokenizes the provided text into text and escape sequences, along with
+/// what kind of escape sequence each one is.
+pub fn parse_tokens(text: &str) -> Vec<ParsedToken> {
+  let mut performer = Performer::new(true);
+  performer.advance(text.as_bytes());
+  performer.mark_end();
+  performer
+    .tokens
+    .into_iter()
+    .zip(performer.kinds)
+    .map(|(token, kind)| ParsedToken {
+      range: token.range,
+      kind,
+    })
+    .collect()
+}
+
+fn parse_params(params: &vte::Params) -> Vec<Vec<u16>> {
+  params.iter().map(|param| param.to_vec()).collect()
+}
+
+fn parse_sgr(params: &vte::Params) -> Vec<SgrAttribute> {
+  let mut attributes = Vec::new();
+  let mut iter = params.iter();
+  while let Some(param) = iter.next() {
+    let attribute = match param[0] {
+      0 => SgrAttribute::Reset,
+      1 => SgrAttribute::Bold,
+      2 => SgrAttribute::Dim,
+      3 => SgrAttribute::Italic,
+      4 => match param.get(1) {
+        Some(0) => SgrAttribute::NotUnderlined,
+        Some(style) => SgrAttribute::Underline(*style),
+        None => SgrAttribute::Underline(1),
+      },
+      5 | 6 => SgrAttribute::Blink,
+      7 => SgrAttribute::Inverse,
+      8 => SgrAttribute::Hidden,
+      9 => SgrAttribute::Strikethrough,
+      21 => SgrAttribute::Underline(2),
+      22 => SgrAttribute::NormalIntensity,
+      23 => SgrAttribute::NotItalic,
+      24 => SgrAttribute::NotUnderlined,
+      25 => SgrAttribute::NotBlinking,
+      27 => SgrAttribute::NotInverse,
+      28 => SgrAttribute::NotHidden,
+      29 => SgrAttribute::NotStrikethrough,
+      code @ 30..=37 => SgrAttribute::Foreground(Color::Ansi(code as u8 - 30)),
+      code @ 90..=97 => {
+        SgrAttribute::Foreground(Color::Ansi(code as u8 - 90 + 8))
+      }
+      39 => SgrAttribute::DefaultForeground,
+      code @ 40..=47 => SgrAttribute::Background(Color::Ansi(code as u8 - 40)),
+      code @ 100..=107 => {
+        SgrAttribute::Background(Color::Ansi(code as u8 - 100 + 8))
+      }
+      49 => SgrAttribute::DefaultBackground,
+      53 => SgrAttribute::Overline,
+      55 => SgrAttribute::NotOverlined,
+      59 => SgrAttribute::DefaultUnderlineColor,
+      code @ (38 | 48 | 58) => match extended_color(param, &mut iter) {
+        Some(color) if code == 38 => SgrAttribute::Foreground(color),
+        Some(color) if code == 48 => SgrAttribute::Background(color),
+        Some(color) => SgrAttribute::UnderlineColor(color),
+        None => SgrAttribute::Unknown(param.to_vec()),
+      },
+      _ => SgrAttribute::Unknown(param.to_vec()),
+    };
+    attributes.push(attribute);
+  }
+  if attributes.is_empty() {
+    attributes.push(SgrAttribute::Reset);
+  }
+  attributes
+}
+
+/// Reads an extended color (ex. `38;5;208` or `38:2::255:0:0`), which is
+/// either in the sub-parameters or the following parameters.
+fn extended_color<'a>(
+  param: &[u16],
+  iter: &mut impl Iterator<Item = &'a [u16]>,
+) -> Option<Color> {
+  let to_u8 = |value: u16| u8::try_from(value).ok();
+  if param.len() > 1 {
+    return match param[1..] {
+      [5, index] => Some(Color::Indexed(to_u8(index)?)),
+      [2, r, g, b] | [2, _, r, g, b] => {
+        Some(Color::Rgb(to_u8(r)?, to_u8(g)?, to_u8(b)?))
+      }
+      _ => None,
+    };
+  }
+  let mut next = || iter.next().map(|param| param[0]);
+  match next()? {
+    5 => Some(Color::Indexed(to_u8(next()?)?)),
+    2 => Some(Color::Rgb(
+      to_u8(next()?)?,
+      to_u8(next()?)?,
+      to_u8(next()?)?,
+    )),
+    _ => None,
+  }
+}
+
+/// A token from `StreamTokenizer`, which borrows from the chunk unless it
+/// started in a previous chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamToken<'a> {
+  pub text: Cow<'a, str>,
+  pub is_escape: bool,
+}
+
+/// Tokenizes text that arrives in chunks of bytes (ex. the output of a
+/// subprocess), where an escape sequence or UTF-8 char may be split
+/// across chunks.
+///
+/// The incomplete end of a chunk is kept until the next chunk completes
+/// it, so the tokens are the same as tokenizing the text all at once,
+/// except that text may be split into more tokens.
+#[derive(Default)]
+pub struct StreamTokenizer {
+  parser: Parser,
+  performer: StreamPerformer,
+  // the bytes of the token that's still being parsed
+  pending: Vec<u8>,
+  // whether the pending bytes are an OSC or DCS string that might be
+  // followed by its terminator (`ESC \`)
+  is_awaiting_string_terminator: bool,
+}
+
+impl StreamTokenizer {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Tokenizes the next chunk of bytes. The tokens end before any
+  /// incomplete escape sequence or char at the end of the chunk.
+  pub fn push<'s, 'a>(&'s mut self, chunk: &'a [u8]) -> StreamTokens<'s, 'a> {
+    let pending_len = self.pending.len();
+    let string_end = if std::mem::take(&mut self.is_awaiting_string_terminator)
+    {
+      Some(pending_len)
+    } else {
+      None
+    };
+    StreamTokens {
+      stream: self,
+      chunk,
+      index: 0,
+      pending_len,
+      token_start: 0,
+      text_end: 0,
+      string_end,
+      queued: VecDeque::new(),
+      is_done: false,
+    }
+  }
+
+  /// Gets what's left of an incomplete escape sequence or char once there
+  /// are no more chunks, then resets the tokenizer.
+  pub fn finish(&mut self) -> Option<StreamToken<'static>> {
+    let pending = std::mem::take(&mut self.pending);
+    let is_escape = std::mem::take(&mut self.is_awaiting_string_terminator)
+      || pending.first() == Some(&0x1b);
+    self.parser = Parser::new();
+    if pending.is_empty() {
+      return None;
+    }
+    Some(StreamToken {
+      text: Cow::Owned(String::from_utf8_lossy(&pending).into_owned()),
+      is_escape,
+    })
+  }
+}
+
+/// The tokens of a chunk pushed to a `StreamTokenizer`.
+///
+/// The rest of the chunk is still tokenized when this is dropped before
+/// reaching the end.
+pub struct StreamTokens<'s, 'a> {
+  stream: &'s mut StreamTokenizer,
+  chunk: &'a [u8],
+  index: usize,
+  // indexes below are into the pending bytes followed by the chunk
+  pending_len: usize,
+  token_start: usize,
+  text_end: usize,
+  // the end of an OSC or DCS string that might be followed by its terminator
+  string_end: Option<usize>,
+  queued: VecDeque<StreamToken<'a>>,
+  is_done: bool,
+}
+
+impl<'a> StreamTokens<'_, 'a> {
+  fn slice(&self, range: Range<usize>) -> Cow<'a, str> {
+    if range.start >= self.pending_len {
+      let chunk: &'a [u8] = self.chunk;
+      return String::from_utf8_lossy(
+        &chunk[range.start - self.pending_len..range.end - self.pending_len],
+      );
+    }
+    let mut bytes = self.stream.pending[range.start..].to_vec();
+    bytes.extend_from_slice(&self.chunk[..range.end - self.pending_len]);
+    Cow::Owned(String::from_utf8_lossy(&bytes).into_owned())
+  }
+
+  fn queue_text(&mut self) {
+    if self.text_end > self.token_start {
+      let text = self.slice(self.token_start..self.text_end);
+      self.queued.push_back(StreamToken {
+        text,
+        is_escape: false,
+      });
+    }
+    self.token_start = self.text_end;
+  }
+
+  fn queue_escape(&mut self, end: usize) {
+    let text = self.slice(self.token_start..end);
+    self.queued.push_back(StreamToken {
+      text,
+      is_escape: true,
+    });
+    self.token_start = end;
+    self.text_end = end;
+  }
+
+  fn advance(&mut self) {
+    let byte = self.chunk[self.index];
+    self.index += 1;
+    let end = self.pending_len + self.index;
+    let stream = &mut *self.stream;
+    stream.parser.advance(&mut stream.performer, byte);
+    let event = stream.performer.event.take();
+
+    if let Some(string_end) = self.string_end.take() {
+      if event == Some(StreamEvent::StringTerminator) {
+        self.queue_escape(end);
+        return;
+      }
+      self.queue_escape(string_end);
+    }
+    match event {
+      Some(StreamEvent::Text) => self.text_end = end,
+      Some(StreamEvent::Escape | StreamEvent::StringTerminator) => {
+        self.queue_text();
+        self.queue_escape(end);
+      }
+      Some(StreamEvent::StringEnd) => {
+        self.queue_text();
+        self.string_end = Some(end);
+      }
+      None => {}
+    }
+  }
+
+  fn finish_chunk(&mut self) {
+    self.is_done = true;
+    if self.string_end.is_none() {
+      self.queue_text();
+    }
+    // keep the incomplete end for the next chunk
+    let chunk_start = self.token_start.saturating_sub(self.pending_len);
+    let mut pending = if self.token_start < self.pending_len {
+      self.stream.pending.split_off(self.token_start)
+    } else {
+      Vec::new()
+    };
+    pending.extend_from_slice(&self.chunk[chunk_start..]);
+    self.stream.pending = pending;
+    self.stream.is_awaiting_string_terminator = self.string_end.is_some();
+  }
+}
+
+impl<'a> Iterator for StreamTokens<'_, 'a> {
+  type Item = StreamToken<'a>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      if let Some(token) = self.queued.pop_front() {
+        return Some(token);
+      }
+      if self.is_done {
+        return None;
+      }
+      if self.index < self.chunk.len() {
+        self.advance();
+      } else {
+        self.finish_chunk();
+      }
+    }
+  }
+}
+
+impl Drop for StreamTokens<'_, '_> {
+  fn drop(&mut self) {
+    while !self.is_done {
+      if self.index < self.chunk.len() {
+        self.advance();
+      } else {
+        self.finish_chunk();
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StreamEvent {
+  Text,
+  Escape,
+  // an OSC or DCS string that's either done or followed by a terminator
+  StringEnd,
+  // `ESC \`, which may terminate the previous string
+  StringTerminator,
+}
+
+#[derive(Default)]
+struct StreamPerformer {
+  event: Option<StreamEvent>,
+}
+
+impl Perform for StreamPerformer {
+  fn print(&mut self, _c: char) {
+    self.event = Some(StreamEvent::Text);
+  }
+
+  fn execute(&mut self, byte: u8) {
+    self.event = Some(match byte {
+      b'\n' | b'\r' | b'\t' => StreamEvent::Text,
+      _ => StreamEvent::Escape,
+    });
+  }
+
+  fn unhook(&mut self) {
+    self.event = Some(StreamEvent::StringEnd);
+  }
+
+  fn osc_dispatch(&mut self, _params: &[&[u8]], bell_terminated: bool) {
+    self.event = Some(if bell_terminated {
+      StreamEvent::Escape
+    } else {
+      StreamEvent::StringEnd
+    });
+  }
+
+  fn csi_dispatch(
+    &mut self,
+    _params: &vte::Params,
+    _intermediates: &[u8],
+    _ignore: bool,
+    _action: char,
+  ) {
+    self.event = Some(StreamEvent::Escape);
+  }
+
+  fn esc_dispatch(&mut self, intermediates: &[u8], _ignore: bool, byte: u8) {
+    self.event = Some(if byte == b'\\' && intermediates.is_empty() {
+      StreamEvent::StringTerminator
+    } else {
+      StreamEvent::Escape
+    });
+  }
+}
+
 struct Performer {
   last_handled_start_index: usize,
   last_handled_end_index: usize,
   current_end_index: usize,
   tokens: Vec<AnsiToken>,
   is_current_escape: bool,
+  // whether the last escape was an OSC or DCS string whose terminator
+  // (`ESC \`) is still being parsed
+  is_awaiting_string_terminator: bool,
+  // the kind of each token, which is only collected when parsing
+  is_parsing_kinds: bool,
+  kinds: Vec<TokenKind>,
+  current_kind: Option<TokenKind>,
+  dcs: Option<TokenKind>,
 }
 
 impl Performer {
+  fn new(is_parsing_kinds: bool) -> Self {
+    Self {
+      last_handled_start_index: 0,
+      last_handled_end_index: 0,
+      current_end_index: 0,
+      tokens: Vec::new(),
+      is_current_escape: false,
+      is_awaiting_string_terminator: false,
+      is_parsing_kinds,
+      kinds: Vec::new(),
+      current_kind: None,
+      dcs: None,
+    }
+  }
+
+  fn advance(&mut self, bytes: &[u8]) {
+    let mut parser = Parser::new();
+    for byte in bytes {
+      self.current_end_index += 1;
+      parser.advance(self, *byte);
+    }
+  }
+
   pub fn mark_char(&mut self, c: char) {
+    self.is_awaiting_string_terminator = false;
     if self.is_current_escape {
       let char_start_index = self.current_end_index - c.len_utf8();
       self.last_handled_start_index = char_start_index;
@@ -69,6 +937,7 @@ impl Performer {
   }
 
   pub fn mark_escape(&mut self) {
+    self.is_awaiting_string_terminator = false;
     if !self.is_current_escape {
       self.finalize(false);
       self.is_current_escape = true;
@@ -79,7 +948,22 @@ impl Performer {
     self.last_handled_start_index = self.current_end_index;
   }
 
+  fn mark_escape_kind(&mut self, kind: impl FnOnce() -> TokenKind) {
+    if self.is_parsing_kinds {
+      self.current_kind = Some(kind());
+    }
+    self.mark_escape();
+  }
+
   pub fn mark_end(&mut self) {
+    if !self.is_current_escape
+      && self.last_handled_end_index < self.current_end_index
+    {
+      // the text is followed by an incomplete escape sequence
+      self.finalize(false);
+      self.is_current_escape = true;
+      self.last_handled_start_index = self.last_handled_end_index;
+    }
     self.last_handled_end_index = self.current_end_index;
     self.finalize(self.is_current_escape);
   }
@@ -88,6 +972,13 @@ impl Performer {
     let range = self.last_handled_start_index..self.last_handled_end_index;
     if !range.is_empty() {
       self.tokens.push(AnsiToken { range, is_escape });
+      if self.is_parsing_kinds {
+        self.kinds.push(if is_escape {
+          self.current_kind.take().unwrap_or(TokenKind::Incomplete)
+        } else {
+          TokenKind::Text
+        });
+      }
     }
   }
 }
@@ -102,44 +993,85 @@ impl Perform for Performer {
       b'\n' => self.mark_char('\n'),
       b'\r' => self.mark_char('\r'),
       b'\t' => self.mark_char('\t'),
-      _ => self.mark_escape(),
+      _ => self.mark_escape_kind(|| TokenKind::Control(byte)),
     }
   }
 
   fn hook(
     &mut self,
-    _params: &vte::Params,
-    _intermediates: &[u8],
+    params: &vte::Params,
+    intermediates: &[u8],
     _ignore: bool,
-    _action: char,
+    action: char,
   ) {
-    self.mark_escape();
+    // the whole string is a single token that ends when it's unhooked
+    if self.is_parsing_kinds {
+      self.dcs = Some(TokenKind::Dcs {
+        params: parse_params(params),
+        intermediates: intermediates.to_vec(),
+        action,
+        data: Vec::new(),
+      });
+    }
   }
 
-  fn put(&mut self, _byte: u8) {
-    self.mark_escape();
+  fn put(&mut self, byte: u8) {
+    if let Some(TokenKind::Dcs { data, .. }) = &mut self.dcs {
+      data.push(byte);
+    }
   }
 
   fn unhook(&mut self) {
-    self.mark_escape();
+    let dcs = self.dcs.take();
+    self.mark_escape_kind(|| dcs.unwrap_or(TokenKind::Incomplete));
+    self.is_awaiting_string_terminator = true;
   }
 
-  fn osc_dispatch(&mut self, _params: &[&[u8]], _bell_terminated: bool) {
-    self.mark_escape();
+  fn osc_dispatch(&mut self, params: &[&[u8]], bell_terminated: bool) {
+    self.mark_escape_kind(|| {
+      TokenKind::Osc(params.iter().map(|param| param.to_vec()).collect())
+    });
+    self.is_awaiting_string_terminator = !bell_terminated;
   }
 
   fn csi_dispatch(
     &mut self,
-    _params: &vte::Params,
-    _intermediates: &[u8],
+    params: &vte::Params,
+    intermediates: &[u8],
     _ignore: bool,
-    _action: char,
+    action: char,
   ) {
-    self.mark_escape();
+    self.mark_escape_kind(|| {
+      if action == 'm' && intermediates.is_empty() {
+        TokenKind::Sgr(parse_sgr(params))
+      } else {
+        TokenKind::Csi {
+          params: parse_params(params),
+          intermediates: intermediates.to_vec(),
+          action,
+        }
+      }
+    });
   }
 
-  fn esc_dispatch(&mut self, _intermediates: &[u8], _ignore: bool, _byte: u8) {
-    self.mark_escape();
+  fn esc_dispatch(&mut self, intermediates: &[u8], _ignore: bool, byte: u8) {
+    if byte == b'\\'
+      && intermediates.is_empty()
+      && self.is_awaiting_string_terminator
+      && let Some(token) = self.tokens.last_mut()
+    {
+      // the string terminator belongs to the preceding OSC or DCS string,
+      // whose token already includes the ESC
+      self.is_awaiting_string_terminator = false;
+      token.range.end = self.current_end_index;
+      self.last_handled_end_index = self.current_end_index;
+      self.last_handled_start_index = self.current_end_index;
+      return;
+    }
+    self.mark_escape_kind(|| TokenKind::Esc {
+      intermediates: intermediates.to_vec(),
+      byte,
+    });
   }
 }
 
@@ -147,7 +1079,144 @@ impl Perform for Performer {
 mod test {
   use pretty_assertions::assert_eq;
 
+  use super::Color;
+  use super::SanitizeMode;
+  use super::SgrAttribute;
+  use super::StreamToken;
+  use super::StreamTokenizer;
+  use super::TokenKind;
+  use super::pad_to_width;
+  use super::parse_tokens;
+  use super::sanitize;
+  use super::slice_columns;
+  use super::strip_ansi_codes;
   use super::tokenize;
+  use super::truncate_to_width;
+  use crate::Align;
+  use crate::WidthProfile;
+
+  #[test]
+  fn strips_horizontal_movements() {
+    assert_eq!(strip_ansi_codes("a\x1b[1Cb\x1b[Cc"), "a b c");
+    assert_eq!(strip_ansi_codes("a\x1b[2Cb\x1b[31m\x1b[0C"), "a  b ");
+    // erasing characters doesn't move the cursor
+    assert_eq!(strip_ansi_codes("a\x1b[3Xb"), "ab");
+    assert_eq!(
+      strip_ansi_codes("ab\x1b[5G\x1b[2Gc\nd\x1b[3Ge"),
+      "ab  c\nd e"
+    );
+  }
+
+  #[test]
+  fn slices_columns() {
+    let text = "ab\x1b[31mcd\x1b[0mef";
+    assert_eq!(slice_columns(text, 0..10, WidthProfile::Narrow), text);
+    assert_eq!(
+      slice_columns("\x1b[31mab", 0..10, WidthProfile::Narrow),
+      "\x1b[31mab\x1b[0m"
+    );
+    assert_eq!(
+      slice_columns(text, 1..3, WidthProfile::Narrow),
+      "b\x1b[31mc\x1b[0m"
+    );
+    assert_eq!(
+      slice_columns(text, 3..5, WidthProfile::Narrow),
+      "\x1b[31md\x1b[0me"
+    );
+    assert_eq!(slice_columns("a🦕b", 2..4, WidthProfile::Narrow), " b");
+    assert_eq!(slice_columns("a🦕b", 0..2, WidthProfile::Narrow), "a ");
+    assert_eq!(
+      slice_columns(
+        "\x1b]8;;https://x.dev\x1b\\link\x1b]8;;\x1b\\",
+        1..3,
+        WidthProfile::Narrow
+      ),
+      "\x1b]8;;https://x.dev\x1b\\in\x1b]8;;\x1b\\"
+    );
+  }
+
+  #[test]
+  fn truncates_and_pads_to_width() {
+    let text = "\x1b[1mbold\x1b[0m text";
+    assert_eq!(truncate_to_width(text, 9, WidthProfile::Narrow), text);
+    assert_eq!(
+      truncate_to_width(text, 5, WidthProfile::Narrow),
+      "\x1b[1mbold\x1b[0m…"
+    );
+    assert_eq!(
+      truncate_to_width(text, 3, WidthProfile::Narrow),
+      "\x1b[1mbo…\x1b[0m"
+    );
+    assert_eq!(
+      truncate_to_width(text, 0, WidthProfile::Narrow),
+      "\x1b[1m\x1b[0m"
+    );
+    assert_eq!(
+      truncate_to_width(
+        "\x1b]8;;https://x.dev\x1b\\\x1b[1mlink",
+        3,
+        WidthProfile::Narrow
+      ),
+      "\x1b]8;;https://x.dev\x1b\\\x1b[1mli…\x1b[0m\x1b]8;;\x1b\\"
+    );
+    assert_eq!(truncate_to_width("αβγ", 4, WidthProfile::Wide), "α…");
+    assert_eq!(
+      truncate_to_width(
+        "\x1b]8;;https://x.dev\x1b\\ab",
+        5,
+        WidthProfile::Narrow
+      ),
+      "\x1b]8;;https://x.dev\x1b\\ab\x1b]8;;\x1b\\"
+    );
+    assert_eq!(
+      pad_to_width("\x1b[41mab", 3, Align::Left, WidthProfile::Narrow),
+      "\x1b[41mab\x1b[0m "
+    );
+    assert_eq!(
+      pad_to_width("\x1b[1mab\x1b[0m", 4, Align::Left, WidthProfile::Narrow),
+      "\x1b[1mab\x1b[0m  "
+    );
+    assert_eq!(
+      pad_to_width("ab", 5, Align::Right, WidthProfile::Narrow),
+      "   ab"
+    );
+    assert_eq!(
+      pad_to_width("ab", 5, Align::Center, WidthProfile::Narrow),
+      " ab  "
+    );
+    assert_eq!(
+      pad_to_width("abc", 2, Align::Center, WidthProfile::Narrow),
+      "abc"
+    );
+  }
+
+  #[test]
+  fn sanitizes() {
+    let text =
+      "\x1b[1;31mred\x1b[0m \x1b]8;;https://x.dev\x1b\\link\x1b]8;;\x1b\\";
+    assert_eq!(sanitize(text, SanitizeMode::Remove), text);
+    let text = "\x1b[2J\x1b]0;title\x07a\x07b\x1b[1Ac\u{9b}2K\td";
+    assert_eq!(sanitize(text, SanitizeMode::Remove), "a^Gbc\td");
+    assert_eq!(
+      sanitize(text, SanitizeMode::Show),
+      "^[[2J^[]0;title^Ga^Gb^[[1Ac^[[2K\td"
+    );
+    assert_eq!(sanitize("a\rb\r\nc", SanitizeMode::Remove), "a^Mb\r\nc");
+    // hyperlinks must be terminated and are closed at the end
+    assert_eq!(sanitize("a\x1b]8;;http://evil", SanitizeMode::Remove), "a");
+    assert_eq!(
+      sanitize("a\x1b]8;;http://e\x1b[2Jvil", SanitizeMode::Remove),
+      "avil"
+    );
+    assert_eq!(
+      sanitize("\x1b]8;;http://x.dev\x07a\x07", SanitizeMode::Remove),
+      "\x1b]8;;http://x.dev\x07a^G\x1b]8;;\x1b\\"
+    );
+    assert!(matches!(
+      sanitize("a\r\nb", SanitizeMode::Remove),
+      std::borrow::Cow::Borrowed(_)
+    ));
+  }
 
   #[test]
   fn should_tokenize() {
@@ -193,6 +1262,136 @@ mod test {
     );
   }
 
+  #[test]
+  fn tokenizes_string_terminated_sequences() {
+    let text = "\x1b]8;;https://x.dev\x1b\\a\x1bP1$r0m\x1b\\b";
+    let output = get_output(text);
+    assert_eq!(
+      output,
+      vec![
+        TestToken {
+          text: "\x1b]8;;https://x.dev\x1b\\".to_string(),
+          is_escape: true,
+        },
+        TestToken {
+          text: "a".to_string(),
+          is_escape: false,
+        },
+        TestToken {
+          text: "\x1bP1$r0m\x1b\\".to_string(),
+          is_escape: true,
+        },
+        TestToken {
+          text: "b".to_string(),
+          is_escape: false,
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn tokenizes_streams() {
+    let text = "a\x1b[1;31mé\x1b[0m🦕\x1b]8;;https://x.dev\x1b\\b\x07\x1bP1$r0m\x1b\\\x1b]0;t\x07c\x1b]8;;\x1b\\";
+    let expected = get_output(text);
+    for chunk_size in 1..text.len() {
+      let mut stream = StreamTokenizer::new();
+      let mut output: Vec<TestToken> = Vec::new();
+      let mut push_token = |token: StreamToken| match output.last_mut() {
+        Some(last) if !last.is_escape && !token.is_escape => {
+          last.text.push_str(&token.text);
+        }
+        _ => output.push(TestToken {
+          text: token.text.into_owned(),
+          is_escape: token.is_escape,
+        }),
+      };
+      for chunk in text.as_bytes().chunks(chunk_size) {
+        stream.push(chunk).for_each(&mut push_token);
+      }
+      stream.finish().into_iter().for_each(&mut push_token);
+      assert_eq!(output, expected, "chunk size {}", chunk_size);
+    }
+
+    let mut stream = StreamTokenizer::new();
+    assert_eq!(stream.push(b"a\x1b[3").count(), 1);
+    assert_eq!(
+      stream.finish(),
+      Some(StreamToken {
+        text: "\x1b[3".into(),
+        is_escape: true,
+      })
+    );
+  }
+
+  #[test]
+  fn parses_token_kinds() {
+    let text = "\x1b[1;38;5;208;48:2::1:2:3mhi\x1b[m\x07\x1b[2;3H\x1b]0;title\x07\x1bP1$r0m\x1b\\\x1b7\x1b[3";
+    let output = parse_tokens(text)
+      .into_iter()
+      .map(|t| (text[t.range].to_string(), t.kind))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      output,
+      vec![
+        (
+          "\x1b[1;38;5;208;48:2::1:2:3m".to_string(),
+          TokenKind::Sgr(vec![
+            SgrAttribute::Bold,
+            SgrAttribute::Foreground(Color::Indexed(208)),
+            SgrAttribute::Background(Color::Rgb(1, 2, 3)),
+          ])
+        ),
+        ("hi".to_string(), TokenKind::Text),
+        (
+          "\x1b[m".to_string(),
+          TokenKind::Sgr(vec![SgrAttribute::Reset])
+        ),
+        ("\x07".to_string(), TokenKind::Control(0x07)),
+        (
+          "\x1b[2;3H".to_string(),
+          TokenKind::Csi {
+            params: vec![vec![2], vec![3]],
+            intermediates: vec![],
+            action: 'H',
+          }
+        ),
+        (
+          "\x1b]0;title\x07".to_string(),
+          TokenKind::Osc(vec![b"0".to_vec(), b"title".to_vec()])
+        ),
+        (
+          "\x1bP1$r0m\x1b\\".to_string(),
+          TokenKind::Dcs {
+            params: vec![vec![1]],
+            intermediates: b"$".to_vec(),
+            action: 'r',
+            data: b"0m".to_vec(),
+          }
+        ),
+        (
+          "\x1b7".to_string(),
+          TokenKind::Esc {
+            intermediates: vec![],
+            byte: b'7',
+          }
+        ),
+        ("\x1b[3".to_string(), TokenKind::Incomplete),
+      ]
+    );
+    let text = "hi\x1b[3";
+    let output = parse_tokens(text)
+      .into_iter()
+      .map(|t| (text[t.range].to_string(), t.kind))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      output,
+      vec![
+        ("hi".to_string(), TokenKind::Text),
+        ("\x1b[3".to_string(), TokenKind::Incomplete),
+      ]
+    );
+  }
+
   #[derive(Debug, PartialEq, Eq)]
   struct TestToken {
     text: String,
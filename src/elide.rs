@@ -0,0 +1,171 @@
+use std::borrow::Cow;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::WidthProfile;
+use crate::ansi::ELLIPSIS;
+
+/// Shortens a file path or URL to fit in `max_width` columns by replacing
+/// directories in the middle with an ellipsis, while keeping the file name
+/// and as much of the end as fits (ex. `~/src/…/crates/foo/lib.rs` or
+/// `https://…/pkg/v1.2.3.tgz`).
+///
+/// When the file name alone doesn't fit, the start of it is elided. The
+/// last directory of a path with a trailing separator is kept like a file
+/// name. The text is measured using the width profile and must not
+/// contain ANSI escape sequences.
+pub fn elide_path(
+  path: &str,
+  max_width: usize,
+  profile: WidthProfile,
+) -> Cow<'_, str> {
+  if profile.str_width(path) <= max_width {
+    return Cow::Borrowed(path);
+  }
+
+  let separator = if !path.contains('/') && path.contains('\\') {
+    '\\'
+  } else {
+    '/'
+  };
+  let sep_width = separator.len_utf8();
+  // the last directory of a path with a trailing separator is kept as if
+  // it was the file name
+  let (path, trailing_width) = match path.strip_suffix(separator) {
+    Some(trimmed) if !trimmed.is_empty() => (trimmed, sep_width),
+    _ => (path, 0),
+  };
+  let (root, rest) = split_root(path, separator);
+  let components: Vec<&str> = rest.split(separator).collect();
+  let ellipsis_width = profile.str_width(ELLIPSIS);
+  let file_name = components[components.len() - 1];
+
+  // root + "…/" + file name is the shortest form that keeps the file name
+  let mut width = profile.str_width(root)
+    + ellipsis_width
+    + sep_width
+    + profile.str_width(file_name)
+    + trailing_width;
+  if components.len() < 2 || width > max_width {
+    return Cow::Owned(elide_start(path, max_width, profile));
+  }
+
+  // keep as much of the end as fits, then as much of the start
+  let mut tail_start = components.len() - 1;
+  while tail_start > 1 {
+    let component_width = profile.str_width(components[tail_start - 1]);
+    if width + component_width + sep_width > max_width {
+      break;
+    }
+    width += component_width + sep_width;
+    tail_start -= 1;
+  }
+  let mut head_end = 0;
+  while head_end < tail_start - 1 {
+    let component_width = profile.str_width(components[head_end]);
+    if width + component_width + sep_width > max_width {
+      break;
+    }
+    width += component_width + sep_width;
+    head_end += 1;
+  }
+
+  let mut text = String::with_capacity(path.len());
+  text.push_str(root);
+  for component in &components[..head_end] {
+    text.push_str(component);
+    text.push(separator);
+  }
+  text.push_str(ELLIPSIS);
+  for component in &components[tail_start..] {
+    text.push(separator);
+    text.push_str(component);
+  }
+  if trailing_width > 0 {
+    text.push(separator);
+  }
+  Cow::Owned(text)
+}
+
+/// Splits off the part that's always kept, such as the scheme of a URL
+/// (`https://`), a home directory (`~/`), or a root (`/` or `C:\`).
+fn split_root(path: &str, separator: char) -> (&str, &str) {
+  let root_len = if let Some(index) = path.find("://") {
+    index + 3
+  } else if path.starts_with(['~', '.'])
+    && let Some(index) = path.find(separator)
+    && path[..index].chars().all(|c| c == '~' || c == '.')
+  {
+    index + 1
+  } else if path.starts_with(separator) {
+    1
+  } else {
+    match path.split_once(separator) {
+      Some((drive, _)) if drive.len() == 2 && drive.ends_with(':') => 3,
+      _ => 0,
+    }
+  };
+  path.split_at(root_len)
+}
+
+/// Keeps the end of the text that fits after an ellipsis.
+fn elide_start(text: &str, max_width: usize, profile: WidthProfile) -> String {
+  let ellipsis_width = profile.str_width(ELLIPSIS);
+  let Some(budget) = max_width.checked_sub(ellipsis_width) else {
+    return String::new();
+  };
+  let mut width = 0;
+  let mut start = text.len();
+  for (index, grapheme) in text.grapheme_indices(true).rev() {
+    width += profile.grapheme_width(grapheme);
+    if width > budget {
+      break;
+    }
+    start = index;
+  }
+  format!("{}{}", ELLIPSIS, &text[start..])
+}
+
+#[cfg(test)]
+mod test {
+  use pretty_assertions::assert_eq;
+
+  use super::elide_path;
+  use crate::WidthProfile::Narrow;
+  use crate::WidthProfile::Wide;
+
+  #[test]
+  fn elides_paths() {
+    let path = "~/src/github/project/crates/foo/lib.rs";
+    assert_eq!(elide_path(path, 100, Narrow), path);
+    assert_eq!(elide_path(path, 25, Narrow), "~/src/…/crates/foo/lib.rs");
+    assert_eq!(elide_path(path, 21, Narrow), "~/…/crates/foo/lib.rs");
+    assert_eq!(elide_path(path, 10, Narrow), "~/…/lib.rs");
+    assert_eq!(elide_path(path, 6, Narrow), "…ib.rs");
+    assert_eq!(elide_path("α/β/γ/δ.rs", 12, Wide), "…/γ/δ.rs");
+    assert_eq!(
+      elide_path("/usr/local/lib/libfoo.so", 20, Narrow),
+      "/usr/…/lib/libfoo.so"
+    );
+    assert_eq!(elide_path("a/b/", 2, Narrow), "…b");
+    assert_eq!(
+      elide_path("~/src/github/project/", 13, Narrow),
+      "~/…/project/"
+    );
+    assert_eq!(
+      elide_path(r"C:\Users\david\AppData\cache.db", 20, Narrow),
+      r"C:\Users\…\cache.db"
+    );
+  }
+
+  #[test]
+  fn elides_urls() {
+    let url = "https://registry.example.com/pkg/v1.2.3.tgz";
+    assert_eq!(elide_path(url, 24, Narrow), "https://…/pkg/v1.2.3.tgz");
+    assert_eq!(elide_path(url, 30, Narrow), "https://…/pkg/v1.2.3.tgz");
+    assert_eq!(
+      elide_path("https://example.com/a/b/c/file.tgz", 30, Narrow),
+      "https://…/a/b/c/file.tgz"
+    );
+  }
+}
@@ -1,22 +1,43 @@
 use std::borrow::Cow;
 use std::io::Write;
 
-use ansi::strip_ansi_codes;
-use unicode_width::UnicodeWidthStr;
+use ansi::strip_ansi_codes_with_profile;
+use sgr::SgrState;
+use truncate::truncate_line;
+use unicode_segmentation::UnicodeSegmentation;
 use word::WordToken;
 use word::tokenize_words;
 
 pub mod ansi;
 #[cfg(feature = "sized")]
 mod console;
+mod elide;
+pub mod markup;
+mod overwrite;
+mod sgr;
+mod style;
+mod truncate;
+mod width;
 mod word;
 
+pub use style::Span;
+pub use style::Style;
+pub use style::StyledLine;
+pub use truncate::Ellipsis;
+pub use width::WidthProfile;
+
 const VTS_MOVE_TO_ZERO_COL: &str = "\x1B[0G";
 const VTS_CLEAR_CURSOR_DOWN: &str = concat!(
   "\x1B[2K", // clear current line
   "\x1B[J",  // clear cursor down
 );
 const VTS_CLEAR_UNTIL_NEWLINE: &str = "\x1B[K";
+const VTS_RESET_STYLE: &str = "\x1B[0m";
+
+const DEFAULT_TAB_WIDTH: usize = 8;
+const DEFAULT_TOO_SMALL_PLACEHOLDER: &str = "↔ widen terminal";
+// used for borrowing runs of spaces when expanding tabs
+const SPACES: &str = "                                ";
 
 fn vts_move_up(count: usize) -> String {
   if count == 0 {
@@ -36,7 +57,20 @@ fn vts_move_down(count: usize) -> String {
 
 pub enum TextItem<'a> {
   Text(Cow<'a, str>),
-  HangingText { text: Cow<'a, str>, indent: u16 },
+  HangingText {
+    text: Cow<'a, str>,
+    indent: u16,
+  },
+  /// Text with additional layout options.
+  WithOptions {
+    text: Cow<'a, str>,
+    options: TextItemOptions<'a>,
+  },
+  /// Alternatives with the minimum console width each needs, ordered from
+  /// richest to simplest (ex. `[#####>----] 45% 12MB/s ETA 3s`, `45% 3s`,
+  /// and `45%`). The first one that fits is displayed, falling back to
+  /// the last, and the richest is displayed when the width is unknown.
+  Responsive(Vec<(u16, TextItem<'a>)>),
 }
 
 impl<'a> TextItem<'a> {
@@ -61,6 +95,259 @@ impl<'a> TextItem<'a> {
       indent,
     }
   }
+
+  /// Creates text with a prefix at the start of every line (ex. `"  │ "`),
+  /// which keeps tree-like and quoted blocks aligned when wrapping.
+  pub fn with_prefix(text: &'a str, prefix: &'a str) -> Self {
+    Self::WithOptions {
+      text: Cow::Borrowed(text),
+      options: TextItemOptions {
+        prefix: Some(Cow::Borrowed(prefix)),
+        ..Default::default()
+      },
+    }
+  }
+
+  pub fn with_prefix_owned(text: String, prefix: String) -> Self {
+    Self::WithOptions {
+      text: Cow::Owned(text),
+      options: TextItemOptions {
+        prefix: Some(Cow::Owned(prefix)),
+        ..Default::default()
+      },
+    }
+  }
+
+  /// Sets the prefix of the first line (ex. `"  ├ "`), which replaces the
+  /// prefix or, when there's no prefix, is displayed before the first line
+  /// only.
+  pub fn with_first_line_prefix(self, first_line_prefix: &'a str) -> Self {
+    self.map_options(&|options| {
+      options.first_line_prefix = Some(Cow::Borrowed(first_line_prefix))
+    })
+  }
+
+  /// Sets how lines wider than the console are displayed.
+  pub fn with_overflow(self, overflow: Overflow) -> Self {
+    self.map_options(&|options| options.overflow = overflow)
+  }
+
+  /// Sets text to display flush against the right edge of the first
+  /// line (ex. `"12.3 MiB / 40 MiB"`).
+  ///
+  /// When both don't fit, the first line is elided using the ellipsis of
+  /// `Overflow::Truncate` (or cut off with `Overflow::Clip`).
+  pub fn with_right_text(self, right_text: &'a str) -> Self {
+    self.map_options(&|options| {
+      options.right_text = Some(Cow::Borrowed(right_text))
+    })
+  }
+
+  pub fn with_right_text_owned(self, right_text: String) -> Self {
+    let right_text: Cow<'a, str> = Cow::Owned(right_text);
+    self.map_options(&|options| options.right_text = Some(right_text.clone()))
+  }
+
+  /// Sets whether the right text is displayed on its own line below the
+  /// wrapped text, instead of eliding the text, when both don't fit.
+  pub fn with_right_text_wrapping(self, value: bool) -> Self {
+    self.map_options(&|options| options.right_text_wraps = value)
+  }
+
+  /// Sets how each line is aligned within the console width.
+  pub fn with_align(self, align: Align) -> Self {
+    self.map_options(&|options| options.align = align)
+  }
+
+  /// Limits the number of lines the item occupies, replacing the lines
+  /// that don't fit with a summary line (ex. `… 37 more lines`).
+  pub fn with_max_height(self, max_height: MaxHeight) -> Self {
+    self.map_options(&|options| options.max_height = Some(max_height))
+  }
+
+  /// Interprets `\r` as moving back to the start of the line, so that the
+  /// text after it overwrites the text before it, and `\b` as erasing the
+  /// previous character. This displays the final state of a line from a
+  /// process's progress output instead of writing the characters raw.
+  ///
+  /// The overwrites are applied when rendering, measuring wide characters
+  /// with the console's width profile, so text that's also sanitized
+  /// displays `\r` and `\b` in caret notation instead.
+  pub fn with_overwrites_applied(self) -> Self {
+    self.map_options(&|options| options.apply_overwrites = true)
+  }
+
+  /// Removes the escape sequences other than SGR sequences and OSC 8
+  /// hyperlinks from untrusted text, which would otherwise move the
+  /// cursor or change the terminal's state.
+  pub fn with_sanitized_text(self, mode: ansi::SanitizeMode) -> Self {
+    self.map_text(&|text| match ansi::sanitize(&text, mode) {
+      Cow::Borrowed(_) => text,
+      Cow::Owned(sanitized) => Cow::Owned(sanitized),
+    })
+  }
+
+  fn map_text(self, f: &dyn Fn(Cow<'a, str>) -> Cow<'a, str>) -> Self {
+    match self {
+      Self::Text(text) => Self::Text(f(text)),
+      Self::HangingText { text, indent } => Self::HangingText {
+        text: f(text),
+        indent,
+      },
+      Self::WithOptions { text, options } => Self::WithOptions {
+        text: f(text),
+        options,
+      },
+      Self::Responsive(alternatives) => Self::Responsive(
+        alternatives
+          .into_iter()
+          .map(|(min_width, item)| (min_width, item.map_text(f)))
+          .collect(),
+      ),
+    }
+  }
+
+  // Updates the layout options, which applies to every alternative of a
+  // responsive item.
+  fn map_options(self, f: &dyn Fn(&mut TextItemOptions<'a>)) -> Self {
+    let (text, mut options) = match self {
+      Self::Responsive(alternatives) => {
+        return Self::Responsive(
+          alternatives
+            .into_iter()
+            .map(|(min_width, item)| (min_width, item.map_options(f)))
+            .collect(),
+        );
+      }
+      Self::Text(text) => (text, Default::default()),
+      Self::HangingText { text, indent } => (
+        text,
+        TextItemOptions {
+          hanging_indent: indent,
+          ..Default::default()
+        },
+      ),
+      Self::WithOptions { text, options } => (text, options),
+    };
+    f(&mut options);
+    Self::WithOptions { text, options }
+  }
+
+  fn layout(&self, terminal_width: Option<usize>) -> ItemLayout<'_> {
+    match self {
+      Self::Text(text) => ItemLayout {
+        text,
+        ..Default::default()
+      },
+      Self::HangingText { text, indent } => ItemLayout {
+        text,
+        hanging_indent: *indent as usize,
+        ..Default::default()
+      },
+      Self::WithOptions { text, options } => ItemLayout {
+        text,
+        hanging_indent: options.hanging_indent as usize,
+        prefix: options.prefix.as_deref(),
+        first_line_prefix: options.first_line_prefix.as_deref(),
+        overflow: options.overflow,
+        align: options.align,
+        right_text: options.right_text.as_deref(),
+        right_text_wraps: options.right_text_wraps,
+        max_height: options.max_height,
+        apply_overwrites: options.apply_overwrites,
+      },
+      Self::Responsive(alternatives) => {
+        let alternative = match terminal_width {
+          Some(width) => alternatives
+            .iter()
+            .find(|(min_width, _)| *min_width as usize <= width)
+            .or(alternatives.last()),
+          None => alternatives.first(),
+        };
+        match alternative {
+          Some((_, item)) => item.layout(terminal_width),
+          None => ItemLayout::default(),
+        }
+      }
+    }
+  }
+}
+
+/// Layout options for a `TextItem::WithOptions`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TextItemOptions<'a> {
+  /// Number of columns to indent wrapped lines.
+  pub hanging_indent: u16,
+  /// Prefix for every line (ex. `"  │ "`), which is used instead of
+  /// the hanging indent when set.
+  pub prefix: Option<Cow<'a, str>>,
+  /// Prefix for the first line, which defaults to `prefix`. When there's
+  /// no `prefix`, only the first line has a prefix.
+  pub first_line_prefix: Option<Cow<'a, str>>,
+  /// How lines wider than the console are displayed.
+  pub overflow: Overflow,
+  /// How each line is aligned within the console width.
+  pub align: Align,
+  /// Text displayed flush against the right edge of the first line.
+  pub right_text: Option<Cow<'a, str>>,
+  /// Whether the right text is displayed on its own line when it
+  /// doesn't fit beside the first line.
+  pub right_text_wraps: bool,
+  /// Maximum number of lines the item occupies.
+  pub max_height: Option<MaxHeight>,
+  /// Whether `\r` and `\b` overwrite the text before them.
+  pub apply_overwrites: bool,
+}
+
+// A borrowed view of how a text item should be laid out.
+#[derive(Default, Clone, Copy)]
+struct ItemLayout<'a> {
+  text: &'a str,
+  hanging_indent: usize,
+  prefix: Option<&'a str>,
+  first_line_prefix: Option<&'a str>,
+  overflow: Overflow,
+  align: Align,
+  right_text: Option<&'a str>,
+  right_text_wraps: bool,
+  max_height: Option<MaxHeight>,
+  apply_overwrites: bool,
+}
+
+/// How a line of text that's wider than the console is displayed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+  /// Wraps the text at line break opportunities such as spaces (default).
+  #[default]
+  WordWrap,
+  /// Wraps the text at any character.
+  CharWrap,
+  /// Keeps the text on a single line, replacing what doesn't fit with
+  /// an ellipsis.
+  Truncate(Ellipsis),
+  /// Keeps the text on a single line, cutting off what doesn't fit.
+  Clip,
+}
+
+/// Maximum number of wrapped lines to display for an item, not including
+/// the summary line of how many lines were hidden.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaxHeight {
+  /// Displays the last lines (ex. for the output of a process).
+  Last(u16),
+  /// Displays the first lines.
+  First(u16),
+}
+
+/// How a line of text is aligned within the console width.
+///
+/// Lines are only aligned when the console width is known.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+  #[default]
+  Left,
+  Right,
+  Center,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -70,11 +357,158 @@ struct Line {
 }
 
 impl Line {
-  pub fn new(text: String) -> Self {
+  pub fn new(text: String, width_profile: WidthProfile) -> Self {
     Self {
       // measure the line width each time in order to not include trailing whitespace
-      char_width: UnicodeWidthStr::width(strip_ansi_codes(&text).as_ref()),
+      char_width: width_profile.text_width(&text),
+      text,
+    }
+  }
+}
+
+// Text displayed alongside a line's content, such as a prefix or a
+// wrap marker.
+#[derive(Debug, Default, Clone, Copy)]
+struct Decoration<'a> {
+  text: &'a str,
+  width: usize,
+  // whether the text leaves a style active that must be reset
+  reset_style: bool,
+}
+
+impl<'a> Decoration<'a> {
+  fn new(text: &'a str, width_profile: WidthProfile) -> Self {
+    let mut state = SgrState::default();
+    state.apply_text(text);
+    Self {
+      text,
+      width: width_profile.text_width(text),
+      reset_style: !state.is_default(),
+    }
+  }
+
+  fn push_to(&self, output: &mut String) {
+    output.push_str(self.text);
+    if self.reset_style {
+      output.push_str(VTS_RESET_STYLE);
+    }
+  }
+}
+
+// The text at the start of a line: a hanging indent or a prefix, followed
+// by the wrap marker on continuation lines.
+#[derive(Debug, Default, Clone, Copy)]
+struct LinePrefix<'a> {
+  indent: usize,
+  text: Decoration<'a>,
+  marker: Decoration<'a>,
+  width: usize,
+}
+
+impl<'a> LinePrefix<'a> {
+  fn spaces(indent: usize) -> Self {
+    Self {
+      indent,
+      width: indent,
+      ..Default::default()
+    }
+  }
+
+  fn text(text: &'a str, width_profile: WidthProfile) -> Self {
+    let text = Decoration::new(text, width_profile);
+    Self {
+      width: text.width,
       text,
+      ..Default::default()
+    }
+  }
+
+  fn with_marker(self, marker: Decoration<'a>) -> Self {
+    Self {
+      marker,
+      width: self.width + marker.width,
+      ..self
+    }
+  }
+
+  fn is_empty(&self) -> bool {
+    self.indent == 0 && self.text.text.is_empty() && self.marker.text.is_empty()
+  }
+}
+
+// The line prefixes and wrap markers of a text item.
+struct ItemPrefixes<'a> {
+  first_line: LinePrefix<'a>,
+  paragraph: LinePrefix<'a>,
+  hanging: LinePrefix<'a>,
+  end_marker: Decoration<'a>,
+}
+
+impl<'a> ItemPrefixes<'a> {
+  fn new(
+    layout: &ItemLayout<'a>,
+    terminal_width: Option<usize>,
+    options: &'a RenderOptions,
+  ) -> Self {
+    // a prefix that leaves no room for the text on narrow terminals is
+    // dropped rather than producing a line per character
+    let fits = |width: usize| terminal_width.is_none_or(|t| width < t);
+    let fit = |prefix: LinePrefix<'a>| {
+      if fits(prefix.width) {
+        prefix
+      } else {
+        LinePrefix::default()
+      }
+    };
+    let marker = |text: &'a Option<Cow<'static, str>>| {
+      Decoration::new(text.as_deref().unwrap_or(""), options.width_profile)
+    };
+    let start_marker = marker(&options.wrap_markers.start);
+    let mut end_marker = marker(&options.wrap_markers.end);
+    let (first_line, paragraph, hanging) = match layout.prefix {
+      Some(prefix) => {
+        let prefix = fit(LinePrefix::text(prefix, options.width_profile));
+        let first_line = layout.first_line_prefix.map_or(prefix, |text| {
+          fit(LinePrefix::text(text, options.width_profile))
+        });
+        (first_line, prefix, prefix)
+      }
+      None => (
+        layout
+          .first_line_prefix
+          .map_or(LinePrefix::default(), |text| {
+            fit(LinePrefix::text(text, options.width_profile))
+          }),
+        LinePrefix::default(),
+        fit(LinePrefix::spaces(layout.hanging_indent)),
+      ),
+    };
+    // every wrapped line needs its prefix, the end marker, and at least
+    // one column of text, so drop the end marker when a prefix is too wide
+    let widest_prefix =
+      first_line.width.max(paragraph.width).max(hanging.width);
+    if !fits(end_marker.width * 2) || !fits(widest_prefix + end_marker.width) {
+      end_marker = Decoration::default();
+    }
+    let marked_hanging = hanging.with_marker(start_marker);
+    Self {
+      first_line,
+      paragraph,
+      hanging: if fits(marked_hanging.width + end_marker.width) {
+        marked_hanging
+      } else {
+        hanging
+      },
+      end_marker,
+    }
+  }
+
+  /// Gets the prefix of the first line of the paragraph at the index.
+  fn first_at(&self, paragraph_index: usize) -> LinePrefix<'a> {
+    if paragraph_index == 0 {
+      self.first_line
+    } else {
+      self.paragraph
     }
   }
 }
@@ -85,19 +519,28 @@ impl Line {
 // height, or paragraphs above a tall item's visible window, never pay the
 // concatenation cost.
 struct PendingLine<'a> {
-  indent: usize,
+  prefix: LinePrefix<'a>,
   segments: Vec<&'a str>,
   total_bytes: usize,
   char_width: usize,
+  // escape sequence restoring the style that was active at the end of
+  // the previous line, and whether the style needs a reset at the end
+  style_prefix: String,
+  reset_style: bool,
+  // displayed after the content when the line is soft wrapped
+  end_marker: Decoration<'a>,
 }
 
 impl<'a> PendingLine<'a> {
-  fn new(indent: usize) -> Self {
+  fn new(prefix: LinePrefix<'a>) -> Self {
     Self {
-      indent,
+      prefix,
       segments: Vec::new(),
       total_bytes: 0,
-      char_width: indent,
+      char_width: prefix.width,
+      style_prefix: String::new(),
+      reset_style: false,
+      end_marker: Decoration::default(),
     }
   }
 
@@ -107,14 +550,83 @@ impl<'a> PendingLine<'a> {
     self.char_width += visible_width;
   }
 
+  /// Pushes the text, replacing each tab with the spaces needed to
+  /// reach the next tab stop from the start of the line.
+  fn push_expanding_tabs(
+    &mut self,
+    s: &'a str,
+    tab_width: usize,
+    measure: impl Fn(&str) -> usize,
+  ) {
+    for (i, piece) in s.split('\t').enumerate() {
+      if i > 0 {
+        self.push_spaces(tab_stop_width(self.char_width, tab_width));
+      }
+      if !piece.is_empty() {
+        self.push_segment(piece, measure(piece));
+      }
+    }
+  }
+
+  fn push_spaces(&mut self, mut count: usize) {
+    while count > 0 {
+      let len = count.min(SPACES.len());
+      self.push_segment(&SPACES[..len], len);
+      count -= len;
+    }
+  }
+
+  /// Makes the line self-contained by re-emitting the style that's active
+  /// at its start and resetting the style at its end when necessary.
+  fn carry_style(&mut self, state: &mut SgrState) {
+    self.style_prefix = state.to_escape();
+    for seg in &self.segments {
+      state.apply_text(seg);
+    }
+    self.reset_style = !state.is_default();
+  }
+
+  /// Indents the line so that it's aligned within the terminal width.
+  fn align(&mut self, align: Align, terminal_width: usize) {
+    let free_width = terminal_width.saturating_sub(self.char_width);
+    let padding = match align {
+      Align::Left => 0,
+      Align::Right => free_width,
+      Align::Center => free_width / 2,
+    };
+    self.prefix.indent += padding;
+    self.char_width += padding;
+  }
+
+  /// Displays the marker after the content to indicate the line wraps.
+  fn mark_wrapped(&mut self, marker: Decoration<'a>) {
+    self.end_marker = marker;
+    self.char_width += marker.width;
+  }
+
   fn into_line(self) -> Line {
-    let mut text = String::with_capacity(self.indent + self.total_bytes);
-    for _ in 0..self.indent {
+    let mut text = String::with_capacity(
+      self.prefix.indent
+        + self.prefix.text.text.len()
+        + self.prefix.marker.text.len()
+        + self.style_prefix.len()
+        + self.total_bytes
+        + self.end_marker.text.len()
+        + VTS_RESET_STYLE.len() * 4,
+    );
+    for _ in 0..self.prefix.indent {
       text.push(' ');
     }
+    self.prefix.text.push_to(&mut text);
+    self.prefix.marker.push_to(&mut text);
+    text.push_str(&self.style_prefix);
     for seg in self.segments {
       text.push_str(seg);
     }
+    if self.reset_style {
+      text.push_str(VTS_RESET_STYLE);
+    }
+    self.end_marker.push_to(&mut text);
     Line {
       char_width: self.char_width,
       text,
@@ -122,10 +634,207 @@ impl<'a> PendingLine<'a> {
   }
 
   fn has_content(&self) -> bool {
-    !self.segments.is_empty() || self.indent > 0
+    !self.segments.is_empty() || !self.prefix.is_empty()
+  }
+}
+
+// A line that text is wrapped onto, which is either displayed or only
+// counted.
+trait WrappedLine<'a> {
+  fn new(prefix: LinePrefix<'a>) -> Self;
+  fn push_segment(&mut self, s: &'a str, visible_width: usize);
+  fn push_expanding_tabs(
+    &mut self,
+    s: &'a str,
+    tab_width: usize,
+    measure: impl Fn(&str) -> usize,
+  );
+  fn push_spaces(&mut self, count: usize);
+  fn has_content(&self) -> bool;
+}
+
+impl<'a> WrappedLine<'a> for PendingLine<'a> {
+  fn new(prefix: LinePrefix<'a>) -> Self {
+    PendingLine::new(prefix)
+  }
+
+  fn push_segment(&mut self, s: &'a str, visible_width: usize) {
+    PendingLine::push_segment(self, s, visible_width);
+  }
+
+  fn push_expanding_tabs(
+    &mut self,
+    s: &'a str,
+    tab_width: usize,
+    measure: impl Fn(&str) -> usize,
+  ) {
+    PendingLine::push_expanding_tabs(self, s, tab_width, measure);
+  }
+
+  fn push_spaces(&mut self, count: usize) {
+    PendingLine::push_spaces(self, count);
+  }
+
+  fn has_content(&self) -> bool {
+    PendingLine::has_content(self)
+  }
+}
+
+// A wrapped line that's only counted.
+struct CountedLine {
+  has_content: bool,
+}
+
+impl<'a> WrappedLine<'a> for CountedLine {
+  fn new(prefix: LinePrefix<'a>) -> Self {
+    Self {
+      has_content: !prefix.is_empty(),
+    }
+  }
+
+  fn push_segment(&mut self, _s: &'a str, _visible_width: usize) {
+    self.has_content = true;
+  }
+
+  fn push_expanding_tabs(
+    &mut self,
+    s: &'a str,
+    _tab_width: usize,
+    _measure: impl Fn(&str) -> usize,
+  ) {
+    self.has_content |= !s.is_empty();
+  }
+
+  fn push_spaces(&mut self, count: usize) {
+    self.has_content |= count > 0;
+  }
+
+  fn has_content(&self) -> bool {
+    self.has_content
+  }
+}
+
+#[derive(Debug, Clone)]
+struct RenderOptions {
+  tab_width: usize,
+  width_profile: WidthProfile,
+  wrap_markers: WrapMarkers,
+  hidden_lines_summary: HiddenLinesSummary,
+  reflow: Reflow,
+  full_width_lines: FullWidthLines,
+  is_color_enabled: bool,
+}
+
+impl Default for RenderOptions {
+  fn default() -> Self {
+    Self {
+      tab_width: DEFAULT_TAB_WIDTH,
+      width_profile: WidthProfile::default(),
+      wrap_markers: WrapMarkers::default(),
+      hidden_lines_summary: HiddenLinesSummary::default(),
+      reflow: Reflow::default(),
+      full_width_lines: FullWidthLines::default(),
+      is_color_enabled: true,
+    }
+  }
+}
+
+/// Markers displayed where a line of text is soft wrapped, which makes
+/// wrapped lines distinguishable from lines ending in a newline.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WrapMarkers {
+  /// Displayed at the end of a line that wraps (ex. `"↩"`).
+  pub end: Option<Cow<'static, str>>,
+  /// Displayed at the start of the line it continues on (ex. `"↪"`).
+  pub start: Option<Cow<'static, str>>,
+}
+
+/// The line displayed in place of the lines hidden by a text item's max
+/// height.
+#[derive(Debug, Clone)]
+pub struct HiddenLinesSummary {
+  /// Applied to the text of the summary.
+  pub style: Style,
+  /// Formats the text of the summary for the number of hidden lines.
+  pub format: fn(usize) -> String,
+}
+
+impl Default for HiddenLinesSummary {
+  fn default() -> Self {
+    Self {
+      style: Style::new().dim(),
+      format: |count| {
+        format!("… {} more line{}", count, if count == 1 { "" } else { "s" })
+      },
+    }
+  }
+}
+
+impl HiddenLinesSummary {
+  fn to_text(&self, count: usize) -> String {
+    let escape = self.style.to_escape();
+    let text = (self.format)(count);
+    if escape.is_empty() {
+      text
+    } else {
+      format!("{}{}{}", escape, text, VTS_RESET_STYLE)
+    }
   }
 }
 
+/// How the terminal rearranges the lines on the screen when its width
+/// changes, which determines how to move back to the start of the text
+/// after a resize.
+///
+/// Every rendered line ends with a hard newline, so terminals that reflow
+/// text never join lines back together when growing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Reflow {
+  /// Each line stays on a single row and is cut off at the new width
+  /// (ex. xterm and the Windows console host).
+  None,
+  /// Lines wider than the new width are wrapped onto additional rows and
+  /// the cursor stays on the row of the cell it was at (ex. VTE based
+  /// terminals, kitty, and Windows Terminal).
+  Rewrap,
+  /// Lines are wrapped like `Rewrap`, but the cursor is assumed to be on
+  /// the last row of the text, which is how the text was moved back to
+  /// before the reflow could be configured.
+  #[default]
+  RewrapToLastRow,
+}
+
+/// How lines that fill the entire console width are handled, since
+/// writing the last column leaves the terminal in a pending wrap state
+/// that some terminals resolve by moving the next newline down a line.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FullWidthLines {
+  /// Writes the lines as is, relying on the newline after each line to
+  /// clear the pending wrap (default).
+  #[default]
+  Write,
+  /// Moves the cursor to the zero column after each line that fills the
+  /// width, which clears the pending wrap before the newline.
+  ClearPendingWrap,
+  /// Lays out the text one column narrower than the console, so that no
+  /// line fills the width.
+  ReserveLastColumn,
+}
+
+/// Which lines are displayed when the text items don't fit in the
+/// console height.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+  /// Displays the last lines (default).
+  #[default]
+  Bottom,
+  /// Displays the first lines.
+  Top,
+  /// Keeps the item at the index visible, displaying as many of the
+  /// items below it and then above it as fit.
+  Item(usize),
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ConsoleSize {
   pub cols: Option<u16>,
@@ -136,7 +845,12 @@ pub struct ConsoleStaticText {
   console_size: Box<dyn (Fn() -> ConsoleSize) + Send + 'static>,
   last_lines: Vec<Line>,
   last_size: ConsoleSize,
+  // whether the cursor was left at the zero column of the last line
+  is_last_cursor_zero_column: bool,
   keep_cursor_zero_column: bool,
+  options: RenderOptions,
+  min_size: ConsoleSize,
+  too_small_placeholder: String,
 }
 
 impl std::fmt::Debug for ConsoleStaticText {
@@ -159,7 +873,14 @@ impl ConsoleStaticText {
         cols: None,
         rows: None,
       },
+      is_last_cursor_zero_column: true,
       keep_cursor_zero_column: true,
+      options: Default::default(),
+      min_size: ConsoleSize {
+        cols: None,
+        rows: None,
+      },
+      too_small_placeholder: DEFAULT_TOO_SMALL_PLACEHOLDER.to_string(),
     }
   }
 
@@ -187,6 +908,69 @@ impl ConsoleStaticText {
     self.keep_cursor_zero_column = value;
   }
 
+  /// Sets the number of columns between tab stops (defaults to 8).
+  ///
+  /// Tabs are expanded to spaces relative to the start of the line,
+  /// including any hanging indentation.
+  pub fn tab_width(&mut self, value: u16) {
+    self.options.tab_width = (value as usize).max(1);
+  }
+
+  /// Sets how text is measured (defaults to `WidthProfile::Narrow`).
+  ///
+  /// This should match how the terminal displays East Asian ambiguous
+  /// width characters. Use `WidthProfile::from_locale()` to detect it.
+  pub fn width_profile(&mut self, value: WidthProfile) {
+    self.options.width_profile = value;
+  }
+
+  /// Sets the markers displayed where lines are soft wrapped (defaults
+  /// to none).
+  ///
+  /// The markers may contain escape sequences for styling them.
+  pub fn wrap_markers(&mut self, value: WrapMarkers) {
+    self.options.wrap_markers = value;
+  }
+
+  /// Sets the line displayed in place of the lines hidden by a text
+  /// item's max height (defaults to a dim `… N more lines`).
+  pub fn hidden_lines_summary(&mut self, value: HiddenLinesSummary) {
+    self.options.hidden_lines_summary = value;
+  }
+
+  /// Sets the minimum console size (defaults to none) below which the
+  /// placeholder is rendered instead of the text.
+  pub fn min_size(&mut self, value: ConsoleSize) {
+    self.min_size = value;
+  }
+
+  /// Sets the text rendered when the console is smaller than the
+  /// minimum size (defaults to `"↔ widen terminal"`).
+  pub fn too_small_placeholder(&mut self, value: &str) {
+    self.too_small_placeholder = value.to_string();
+  }
+
+  /// Sets how the terminal rearranges lines when resized (defaults to
+  /// `Reflow::RewrapToLastRow`).
+  pub fn reflow(&mut self, value: Reflow) {
+    self.options.reflow = value;
+  }
+
+  /// Sets how lines that fill the entire console width are handled
+  /// (defaults to `FullWidthLines::Write`).
+  pub fn full_width_lines(&mut self, value: FullWidthLines) {
+    self.options.full_width_lines = value;
+  }
+
+  /// Sets whether styles (colors, text attributes, and hyperlinks) are
+  /// displayed (defaults to true).
+  ///
+  /// When disabled, the SGR and OSC 8 escape sequences are removed from
+  /// the rendered text.
+  pub fn color(&mut self, value: bool) {
+    self.options.is_color_enabled = value;
+  }
+
   pub fn console_size(&self) -> ConsoleSize {
     (self.console_size)()
   }
@@ -207,10 +991,10 @@ impl ConsoleStaticText {
     &mut self,
     size: ConsoleSize,
   ) -> Option<String> {
-    let last_lines = self.get_last_lines(size);
+    let (last_lines, rows_below_cursor) = self.get_last_lines(size);
     if !last_lines.is_empty() {
       let mut text = VTS_MOVE_TO_ZERO_COL.to_string();
-      let move_up_count = last_lines.len() - 1;
+      let move_up_count = last_lines.len() - 1 - rows_below_cursor;
       if move_up_count > 0 {
         text.push_str(&vts_move_up(move_up_count));
       }
@@ -273,6 +1057,34 @@ impl ConsoleStaticText {
     Ok(())
   }
 
+  /// Prints the text items, displaying the lines chosen by the anchor
+  /// when they don't fit in the console height.
+  pub fn eprint_items_with_anchor<'a>(
+    &mut self,
+    text_items: impl DoubleEndedIterator<Item = &'a TextItem<'a>>,
+    anchor: Anchor,
+  ) -> std::io::Result<()> {
+    self.eprint_items_with_size_and_anchor(
+      text_items,
+      self.console_size(),
+      anchor,
+    )
+  }
+
+  pub fn eprint_items_with_size_and_anchor<'a>(
+    &mut self,
+    text_items: impl DoubleEndedIterator<Item = &'a TextItem<'a>>,
+    size: ConsoleSize,
+    anchor: Anchor,
+  ) -> std::io::Result<()> {
+    if let Some(text) =
+      self.render_items_with_size_and_anchor(text_items, size, anchor)
+    {
+      std::io::stderr().write_all(text.as_bytes())?;
+    }
+    Ok(())
+  }
+
   pub fn render_items<'a>(
     &mut self,
     text_items: impl DoubleEndedIterator<Item = &'a TextItem<'a>>,
@@ -284,10 +1096,39 @@ impl ConsoleStaticText {
     &mut self,
     text_items: impl DoubleEndedIterator<Item = &'a TextItem<'a>>,
     size: ConsoleSize,
+  ) -> Option<String> {
+    self.render_items_with_size_and_anchor(text_items, size, Anchor::Bottom)
+  }
+
+  /// Renders the text items, displaying the lines chosen by the anchor
+  /// when they don't fit in the console height.
+  pub fn render_items_with_anchor<'a>(
+    &mut self,
+    text_items: impl DoubleEndedIterator<Item = &'a TextItem<'a>>,
+    anchor: Anchor,
+  ) -> Option<String> {
+    self.render_items_with_size_and_anchor(
+      text_items,
+      self.console_size(),
+      anchor,
+    )
+  }
+
+  pub fn render_items_with_size_and_anchor<'a>(
+    &mut self,
+    text_items: impl DoubleEndedIterator<Item = &'a TextItem<'a>>,
+    size: ConsoleSize,
+    anchor: Anchor,
   ) -> Option<String> {
     let is_terminal_different_size = size != self.last_size;
-    let last_lines = self.get_last_lines(size);
-    let new_lines = render_items(text_items, size);
+    let (last_lines, rows_below_cursor) = self.get_last_lines(size);
+    let new_lines = if self.is_too_small(size) {
+      let placeholder = TextItem::new(&self.too_small_placeholder)
+        .with_overflow(Overflow::Truncate(Ellipsis::End));
+      render_items([placeholder].iter(), size, anchor, &self.options)
+    } else {
+      render_items(text_items, size, anchor, &self.options)
+    };
     // new_lines are already wrapped to the terminal width and truncated
     // to the height by render_items, so we only need to ANSI-strip the
     // text to mirror what raw_render_last_items would produce.
@@ -295,94 +1136,152 @@ impl ConsoleStaticText {
       .iter()
       .map(|line| Line {
         char_width: line.char_width,
-        text: strip_ansi_codes(&line.text).into_owned(),
+        text: strip_ansi_codes_with_profile(
+          &line.text,
+          self.options.width_profile,
+        )
+        .into_owned(),
       })
       .collect();
-    let result =
-      if !are_collections_equal(&last_lines, &last_lines_for_new_lines) {
-        let mut text = String::new();
-        text.push_str(VTS_MOVE_TO_ZERO_COL);
-        if last_lines.len() > 1 {
-          text.push_str(&vts_move_up(last_lines.len() - 1));
-        }
-        if is_terminal_different_size {
-          text.push_str(VTS_CLEAR_CURSOR_DOWN);
-        }
-        for (i, new_line) in new_lines.iter().enumerate() {
-          if i > 0 {
-            text.push_str("\r\n");
-          }
-          text.push_str(&new_line.text);
-          if !is_terminal_different_size
-            && let Some(last_line) = last_lines.get(i)
-            && last_line.char_width > new_line.char_width
-          {
-            text.push_str(VTS_CLEAR_UNTIL_NEWLINE);
-          }
+    // the text must be rendered again when the cursor isn't on the last
+    // line, even when the lines are the same
+    let result = if rows_below_cursor > 0
+      || !are_collections_equal(&last_lines, &last_lines_for_new_lines)
+    {
+      let mut text = String::new();
+      text.push_str(VTS_MOVE_TO_ZERO_COL);
+      if last_lines.len() > 1 {
+        text.push_str(&vts_move_up(last_lines.len() - 1 - rows_below_cursor));
+      }
+      if is_terminal_different_size {
+        text.push_str(VTS_CLEAR_CURSOR_DOWN);
+      }
+      for (i, new_line) in new_lines.iter().enumerate() {
+        if i > 0 {
+          text.push_str("\r\n");
         }
-        if last_lines.len() > new_lines.len() {
-          text.push_str(&vts_move_down(1));
-          text.push_str(VTS_CLEAR_CURSOR_DOWN);
-          text.push_str(&vts_move_up(1));
+        text.push_str(&new_line.text);
+        if !is_terminal_different_size
+          && let Some(last_line) = last_lines.get(i)
+          && last_line.char_width > new_line.char_width
+        {
+          text.push_str(VTS_CLEAR_UNTIL_NEWLINE);
         }
-        if self.keep_cursor_zero_column {
+        if self.options.full_width_lines == FullWidthLines::ClearPendingWrap
+          && i + 1 < new_lines.len()
+          && size
+            .cols
+            .is_some_and(|cols| new_line.char_width >= cols as usize)
+        {
           text.push_str(VTS_MOVE_TO_ZERO_COL);
         }
-        Some(text)
-      } else {
-        None
-      };
+      }
+      if last_lines.len() > new_lines.len() {
+        text.push_str(&vts_move_down(1));
+        text.push_str(VTS_CLEAR_CURSOR_DOWN);
+        text.push_str(&vts_move_up(1));
+      }
+      if self.keep_cursor_zero_column {
+        text.push_str(VTS_MOVE_TO_ZERO_COL);
+      }
+      self.is_last_cursor_zero_column = self.keep_cursor_zero_column;
+      Some(text)
+    } else {
+      None
+    };
     self.last_lines = last_lines_for_new_lines;
     self.last_size = size;
     result
   }
 
-  fn get_last_lines(&mut self, size: ConsoleSize) -> Vec<Line> {
+  fn is_too_small(&self, size: ConsoleSize) -> bool {
+    let is_below = |value: Option<u16>, min: Option<u16>| {
+      value.zip(min).is_some_and(|(value, min)| value < min)
+    };
+    is_below(size.cols, self.min_size.cols)
+      || is_below(size.rows, self.min_size.rows)
+  }
+
+  /// Gets the lines of the last render as they're now displayed on the
+  /// screen, along with the number of those lines below the cursor.
+  fn get_last_lines(&mut self, size: ConsoleSize) -> (Vec<Line>, usize) {
     if size == self.last_size {
-      std::mem::take(&mut self.last_lines)
-    } else {
-      // render the last text with the current terminal width
-      let line_texts = self
-        .last_lines
-        .drain(..)
-        .map(|l| l.text)
-        .collect::<Vec<_>>();
-      let text = line_texts.join("\n");
-      raw_render_last_items(&text, size)
+      return (std::mem::take(&mut self.last_lines), 0);
     }
+    // model how the terminal reflowed the last lines to the current size
+    let line_texts = self
+      .last_lines
+      .drain(..)
+      .map(|l| l.text)
+      .collect::<Vec<_>>();
+    // a cursor at the zero column of a rewrapped last line stays on
+    // the line's first row
+    let rows_below_cursor = match line_texts.last() {
+      Some(last_text)
+        if self.is_last_cursor_zero_column
+          && self.options.reflow == Reflow::Rewrap =>
+      {
+        let size = ConsoleSize {
+          cols: size.cols,
+          rows: None,
+        };
+        raw_render_last_items(last_text, size, &self.options)
+          .len()
+          .saturating_sub(1)
+      }
+      _ => 0,
+    };
+    let text = line_texts.join("\n");
+    let lines = raw_render_last_items(&text, size, &self.options);
+    // no lines are left when the console has no rows or columns
+    let rows_below_cursor =
+      rows_below_cursor.min(lines.len().saturating_sub(1));
+    (lines, rows_below_cursor)
   }
 }
 
-fn raw_render_last_items(text: &str, size: ConsoleSize) -> Vec<Line> {
+fn raw_render_last_items(
+  text: &str,
+  size: ConsoleSize,
+  options: &RenderOptions,
+) -> Vec<Line> {
   let mut lines = Vec::new();
-  let text = strip_ansi_codes(text);
+  let width_profile = options.width_profile;
+  let text = strip_ansi_codes_with_profile(text, width_profile);
   if let Some(terminal_width) = size.cols.map(|c| c as usize) {
     for line in text.split('\n') {
       if line.is_empty() {
-        lines.push(Line::new(String::new()));
+        lines.push(Line::new(String::new(), width_profile));
         continue;
       }
       let mut count = 0;
       let mut current_line = String::new();
-      for c in line.chars() {
-        if let Some(width) = unicode_width::UnicodeWidthChar::width(c) {
-          if count + width > terminal_width {
-            lines.push(Line::new(current_line));
-            current_line = c.to_string();
-            count = width;
-          } else {
-            count += width;
-            current_line.push(c);
+      for grapheme in line.graphemes(true) {
+        let width = if grapheme == "\t" {
+          tab_stop_width(count, options.tab_width)
+        } else {
+          width_profile.grapheme_width(grapheme)
+        };
+        if count + width > terminal_width {
+          if options.reflow == Reflow::None {
+            // the rest of the line is cut off
+            break;
           }
+          lines.push(Line::new(current_line, width_profile));
+          current_line = grapheme.to_string();
+          count = width;
+        } else {
+          count += width;
+          current_line.push_str(grapheme);
         }
       }
       if !current_line.is_empty() {
-        lines.push(Line::new(current_line));
+        lines.push(Line::new(current_line, width_profile));
       }
     }
   } else {
     for line in text.split('\n') {
-      lines.push(Line::new(line.to_string()));
+      lines.push(Line::new(line.to_string(), width_profile));
     }
   }
   truncate_lines_height(lines, size)
@@ -391,10 +1290,85 @@ fn raw_render_last_items(text: &str, size: ConsoleSize) -> Vec<Line> {
 fn render_items<'a>(
   text_items: impl DoubleEndedIterator<Item = &'a TextItem<'a>>,
   size: ConsoleSize,
+  anchor: Anchor,
+  options: &RenderOptions,
 ) -> Vec<Line> {
-  let terminal_width = size.cols.map(|c| c as usize);
+  let terminal_width = size.cols.map(|c| match options.full_width_lines {
+    FullWidthLines::ReserveLastColumn => (c as usize).saturating_sub(1).max(1),
+    FullWidthLines::Write | FullWidthLines::ClearPendingWrap => c as usize,
+  });
   let terminal_height = size.rows.map(|c| c as usize);
+  let lines = match anchor {
+    Anchor::Bottom => render_items_from_bottom(
+      text_items,
+      terminal_width,
+      terminal_height,
+      options,
+    ),
+    Anchor::Top => render_items_from_top(
+      text_items,
+      terminal_width,
+      terminal_height,
+      options,
+    ),
+    Anchor::Item(index) => {
+      let items = text_items.collect::<Vec<_>>();
+      if index < items.len() {
+        // display the item, then as many items below it as fit, then
+        // as many items above it as fit
+        let mut lines = render_items_from_bottom(
+          [items[index]].into_iter(),
+          terminal_width,
+          terminal_height,
+          options,
+        );
+        lines.extend(render_items_from_top(
+          items[index + 1..].iter().copied(),
+          terminal_width,
+          terminal_height.map(|h| h - lines.len()),
+          options,
+        ));
+        let mut above_lines = render_items_from_bottom(
+          items[..index].iter().copied(),
+          terminal_width,
+          terminal_height.map(|h| h - lines.len()),
+          options,
+        );
+        above_lines.extend(lines);
+        above_lines
+      } else {
+        render_items_from_bottom(
+          items.into_iter(),
+          terminal_width,
+          terminal_height,
+          options,
+        )
+      }
+    }
+  };
 
+  // ensure there's always 1 line
+  if lines.is_empty() {
+    vec![Line::new(String::new(), options.width_profile)]
+  } else if !options.is_color_enabled {
+    lines
+      .into_iter()
+      .map(|line| Line {
+        text: ansi::strip_styles(&line.text).into_owned(),
+        char_width: line.char_width,
+      })
+      .collect()
+  } else {
+    lines
+  }
+}
+
+fn render_items_from_bottom<'a>(
+  text_items: impl DoubleEndedIterator<Item = &'a TextItem<'a>>,
+  terminal_width: Option<usize>,
+  terminal_height: Option<usize>,
+  options: &RenderOptions,
+) -> Vec<Line> {
   // process items bottom-up so thousands of text items don't force
   // rendering work we'd immediately truncate away. accumulate lines
   // in reverse, stopping once we've filled the console height.
@@ -409,17 +1383,14 @@ fn render_items<'a>(
     {
       break;
     }
-    let (text, indent) = match item {
-      TextItem::Text(text) => (text.as_ref(), 0usize),
-      TextItem::HangingText { text, indent } => {
-        (text.as_ref(), *indent as usize)
-      }
+    let limit = match terminal_height {
+      Some(h) => LineLimit::Last(h - rev_lines.len()),
+      None => LineLimit::All,
     };
-    let remaining = terminal_height.map(|h| h - rev_lines.len());
-    let pending =
-      render_text_to_pending(text, indent, terminal_width, remaining);
-    for pl in pending.into_iter().rev() {
-      rev_lines.push(pl.into_line());
+    let lines =
+      render_item(&item.layout(terminal_width), terminal_width, limit, options);
+    for line in lines.into_iter().rev() {
+      rev_lines.push(line);
       if let Some(h) = terminal_height
         && rev_lines.len() >= h
       {
@@ -428,14 +1399,179 @@ fn render_items<'a>(
     }
   }
   rev_lines.reverse();
-  let lines = rev_lines;
+  rev_lines
+}
 
-  // ensure there's always 1 line
-  if lines.is_empty() {
-    vec![Line::new(String::new())]
+fn render_items_from_top<'a>(
+  text_items: impl Iterator<Item = &'a TextItem<'a>>,
+  terminal_width: Option<usize>,
+  terminal_height: Option<usize>,
+  options: &RenderOptions,
+) -> Vec<Line> {
+  let mut lines: Vec<Line> = Vec::new();
+  'outer: for item in text_items {
+    if let Some(h) = terminal_height
+      && lines.len() >= h
+    {
+      break;
+    }
+    let limit = match terminal_height {
+      Some(h) => LineLimit::First(h - lines.len()),
+      None => LineLimit::All,
+    };
+    for line in
+      render_item(&item.layout(terminal_width), terminal_width, limit, options)
+    {
+      lines.push(line);
+      if let Some(h) = terminal_height
+        && lines.len() >= h
+      {
+        break 'outer;
+      }
+    }
+  }
+  lines
+}
+
+// Lays out a text item into the lines to display, including the summary
+// line of the lines hidden by its max height.
+fn render_item(
+  layout: &ItemLayout,
+  terminal_width: Option<usize>,
+  limit: LineLimit,
+  options: &RenderOptions,
+) -> Vec<Line> {
+  let overwritten;
+  let layout =
+    if layout.apply_overwrites && layout.text.contains(['\r', '\x08']) {
+      overwritten =
+        overwrite::apply_overwrites(layout.text, options.width_profile);
+      &ItemLayout {
+        text: &overwritten,
+        ..*layout
+      }
+    } else {
+      layout
+    };
+  let (pending, summary) =
+    render_item_to_pending(layout, terminal_width, limit, options);
+  let mut lines = Vec::with_capacity(pending.len() + 1);
+  let pending = pending.into_iter().map(PendingLine::into_line);
+  if let Some(MaxHeight::First(_)) = layout.max_height {
+    lines.extend(pending);
+    lines.extend(summary);
   } else {
-    lines
+    lines.extend(summary);
+    lines.extend(pending);
   }
+  lines
+}
+
+// Produces the pending lines for a text item along with the summary line
+// of the lines hidden by its max height, if any.
+fn render_item_to_pending<'a>(
+  layout: &ItemLayout<'a>,
+  terminal_width: Option<usize>,
+  limit: LineLimit,
+  options: &'a RenderOptions,
+) -> (Vec<PendingLine<'a>>, Option<Line>) {
+  let Some(max_height) = layout.max_height else {
+    let lines = render_text_to_pending(layout, terminal_width, limit, options);
+    return (lines, None);
+  };
+  let height = match max_height {
+    MaxHeight::Last(height) | MaxHeight::First(height) => height as usize,
+  };
+  let line_count = count_item_lines(layout, terminal_width, options);
+  if line_count <= height {
+    let lines = render_text_to_pending(layout, terminal_width, limit, options);
+    return (lines, None);
+  }
+  // only the visible lines within the remaining rows are laid out
+  let visible_limit = match (max_height, limit) {
+    (MaxHeight::Last(_), LineLimit::Last(max)) => {
+      LineLimit::Last(height.min(max))
+    }
+    (MaxHeight::Last(_), _) => LineLimit::Last(height),
+    (MaxHeight::First(_), LineLimit::First(max)) => {
+      LineLimit::First(height.min(max))
+    }
+    (MaxHeight::First(_), _) => LineLimit::First(height),
+  };
+  let lines =
+    render_text_to_pending(layout, terminal_width, visible_limit, options);
+  let summary = options.hidden_lines_summary.to_text(line_count - height);
+  let summary = match terminal_width {
+    Some(terminal_width) => truncate_line(
+      &summary,
+      LinePrefix::default(),
+      terminal_width,
+      Some(Ellipsis::End),
+      options.tab_width,
+      options.width_profile,
+    )
+    .into_line(),
+    None => Line::new(summary, options.width_profile),
+  };
+  (lines, Some(summary))
+}
+
+// Counts the lines of a text item without building them.
+fn count_item_lines(
+  layout: &ItemLayout,
+  terminal_width: Option<usize>,
+  options: &RenderOptions,
+) -> usize {
+  let text = layout.text;
+  if text.is_empty() {
+    return 0;
+  }
+  let paragraph_count = text.split_terminator('\n').count();
+  let Some(terminal_width) = terminal_width else {
+    return paragraph_count;
+  };
+  let prefixes = ItemPrefixes::new(layout, Some(terminal_width), options);
+  text
+    .split_terminator('\n')
+    .enumerate()
+    .map(|(i, p)| {
+      let p = if i + 1 < paragraph_count {
+        p.strip_suffix('\r').unwrap_or(p)
+      } else {
+        p
+      };
+      let first_prefix = prefixes.first_at(i);
+      let wrap_width = WrapWidth::new(
+        p,
+        terminal_width - prefixes.end_marker.width,
+        terminal_width,
+      );
+      let count = match layout.overflow {
+        _ if i == 0 && layout.right_text.is_some() => {
+          compose_right_text(p, layout, &prefixes, terminal_width, options)
+            .len()
+        }
+        Overflow::WordWrap => wrap_paragraph::<CountedLine>(
+          p,
+          first_prefix,
+          prefixes.hanging,
+          wrap_width,
+          options,
+        )
+        .len(),
+        Overflow::CharWrap => char_wrap_paragraph::<CountedLine>(
+          p,
+          first_prefix,
+          prefixes.hanging,
+          wrap_width,
+          options,
+        )
+        .len(),
+        Overflow::Truncate(_) | Overflow::Clip => 1,
+      };
+      count.max(1)
+    })
+    .sum()
 }
 
 fn truncate_lines_height(mut lines: Vec<Line>, size: ConsoleSize) -> Vec<Line> {
@@ -448,20 +1584,43 @@ fn truncate_lines_height(mut lines: Vec<Line>, size: ConsoleSize) -> Vec<Line> {
   lines
 }
 
+// Which lines of a text item to lay out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineLimit {
+  All,
+  Last(usize),
+  First(usize),
+}
+
+impl LineLimit {
+  fn max(self) -> Option<usize> {
+    match self {
+      Self::All => None,
+      Self::Last(max) | Self::First(max) => Some(max),
+    }
+  }
+}
+
 // Produces pending lines for a single text item, processing paragraphs
-// (newline-delimited segments) bottom-up and stopping once `max_lines` is
-// satisfied. This means early paragraphs of a tall item are never word-wrapped
-// if they'd be truncated away anyway.
+// (newline-delimited segments) bottom-up, or top-down for
+// `LineLimit::First`, and stopping once the limit is satisfied. This means
+// early paragraphs of a tall item are never word-wrapped if they'd be
+// truncated away anyway.
 fn render_text_to_pending<'a>(
-  text: &'a str,
-  hanging_indent: usize,
+  layout: &ItemLayout<'a>,
   terminal_width: Option<usize>,
-  max_lines: Option<usize>,
+  limit: LineLimit,
+  options: &'a RenderOptions,
 ) -> Vec<PendingLine<'a>> {
+  let text = layout.text;
+  let max_lines = limit.max();
+  let from_top = matches!(limit, LineLimit::First(_));
   if text.is_empty() || max_lines == Some(0) {
     return Vec::new();
   }
 
+  let prefixes = ItemPrefixes::new(layout, terminal_width, options);
+
   let paragraphs: Vec<&'a str> = text.split_terminator('\n').collect();
   // paragraph i was preceded by `\n` in the source when i > 0; when it's a
   // middle/early paragraph the trailing `\r` belongs to a CRLF pair and must
@@ -475,131 +1634,398 @@ fn render_text_to_pending<'a>(
     }
   };
 
-  match terminal_width {
+  // lines of the first rendered paragraph that were wrapped but are above
+  // the visible window — only kept when needed for tracking styles
+  let has_escapes = text.as_bytes().contains(&0x1b);
+  let mut hidden_lines: Vec<PendingLine<'a>> = Vec::new();
+  let (mut result, first_paragraph) = match terminal_width {
     None => {
       // no wrapping — each paragraph is exactly one line
-      let start =
-        max_lines.map_or(0, |max| paragraphs.len().saturating_sub(max));
-      (start..paragraphs.len())
+      let range = match limit {
+        LineLimit::All => 0..paragraphs.len(),
+        LineLimit::Last(max) => {
+          paragraphs.len().saturating_sub(max)..paragraphs.len()
+        }
+        LineLimit::First(max) => 0..max.min(paragraphs.len()),
+      };
+      let start = range.start;
+      let lines = range
         .map(|i| {
-          let p = paragraph_at(i);
-          let width = UnicodeWidthStr::width(strip_ansi_codes(p).as_ref());
-          let mut pl = PendingLine::new(0);
-          if !p.is_empty() {
-            pl.push_segment(p, width);
+          let mut pl = PendingLine::new(prefixes.first_at(i));
+          pl.push_expanding_tabs(paragraph_at(i), options.tab_width, |s| {
+            options.width_profile.text_width(s)
+          });
+          if i == 0
+            && let Some(right_text) = layout.right_text
+          {
+            pl.push_spaces(1);
+            pl.push_segment(
+              right_text,
+              options.width_profile.text_width(right_text),
+            );
           }
           pl
         })
-        .collect()
+        .collect();
+      (lines, start)
     }
     Some(terminal_width) => {
       let mut result: Vec<PendingLine<'a>> = Vec::new();
-      'outer: for i in (0..paragraphs.len()).rev() {
+      let mut first_paragraph = if from_top { 0 } else { paragraphs.len() };
+      'outer: for step in 0..paragraphs.len() {
+        let i = if from_top {
+          step
+        } else {
+          paragraphs.len() - 1 - step
+        };
+        if !from_top {
+          first_paragraph = i;
+        }
         let p = paragraph_at(i);
-        let mut paragraph_lines =
-          wrap_paragraph(p, hanging_indent, terminal_width);
+        let first_prefix = prefixes.first_at(i);
+        // the last line of a paragraph doesn't display the end marker
+        let wrap_width = WrapWidth::new(
+          p,
+          terminal_width - prefixes.end_marker.width,
+          terminal_width,
+        );
+        let mut paragraph_lines = match layout.overflow {
+          _ if i == 0 && layout.right_text.is_some() => {
+            compose_right_text(p, layout, &prefixes, terminal_width, options)
+          }
+          Overflow::WordWrap => wrap_paragraph(
+            p,
+            first_prefix,
+            prefixes.hanging,
+            wrap_width,
+            options,
+          ),
+          Overflow::CharWrap => char_wrap_paragraph(
+            p,
+            first_prefix,
+            prefixes.hanging,
+            wrap_width,
+            options,
+          ),
+          Overflow::Truncate(ellipsis) => vec![truncate_line(
+            p,
+            first_prefix,
+            terminal_width,
+            Some(ellipsis),
+            options.tab_width,
+            options.width_profile,
+          )],
+          Overflow::Clip => vec![truncate_line(
+            p,
+            first_prefix,
+            terminal_width,
+            None,
+            options.tab_width,
+            options.width_profile,
+          )],
+        };
         if paragraph_lines.is_empty() {
           // an empty or whitespace-only paragraph still occupies one line
-          paragraph_lines.push(PendingLine::new(0));
+          paragraph_lines.push(PendingLine::new(first_prefix));
+        }
+        if let [wrapped @ .., _] = paragraph_lines.as_mut_slice() {
+          for line in wrapped {
+            line.mark_wrapped(prefixes.end_marker);
+          }
         }
-        for pl in paragraph_lines.into_iter().rev() {
+        if from_top {
+          paragraph_lines.reverse();
+        }
+        while let Some(pl) = paragraph_lines.pop() {
           result.push(pl);
           if let Some(max) = max_lines
             && result.len() >= max
           {
+            if has_escapes && !from_top {
+              hidden_lines = paragraph_lines;
+            }
             break 'outer;
           }
         }
       }
-      result.reverse();
-      result
+      if !from_top {
+        result.reverse();
+      }
+      if layout.align != Align::Left {
+        for line in &mut result {
+          line.align(layout.align, terminal_width);
+        }
+      }
+      (result, first_paragraph)
+    }
+  };
+
+  if has_escapes {
+    // restore the style at the start of each line so that lines are
+    // self-contained, even when the lines above aren't displayed
+    let mut state = SgrState::default();
+    let first_paragraph_start = paragraphs[..first_paragraph]
+      .iter()
+      .map(|p| p.len() + 1)
+      .sum::<usize>();
+    state.apply_text(&text[..first_paragraph_start]);
+    for line in &hidden_lines {
+      for seg in &line.segments {
+        state.apply_text(seg);
+      }
+    }
+    for line in &mut result {
+      line.carry_style(&mut state);
     }
   }
+  result
 }
 
-fn wrap_paragraph<'a>(
+// Lays out the first paragraph of an item with its right text flush
+// against the right edge, eliding or wrapping the paragraph when both
+// don't fit on the line.
+fn compose_right_text<'a>(
   text: &'a str,
-  hanging_indent: usize,
+  layout: &ItemLayout<'a>,
+  prefixes: &ItemPrefixes<'a>,
   terminal_width: usize,
+  options: &RenderOptions,
 ) -> Vec<PendingLine<'a>> {
-  let mut lines: Vec<PendingLine<'a>> = Vec::new();
-  let mut current_line = PendingLine::new(0);
-  let mut line_width: usize = 0;
+  let right_text = layout.right_text.unwrap_or("");
+  let right_width = options.width_profile.text_width(right_text);
+  let ellipsis = match layout.overflow {
+    Overflow::Truncate(ellipsis) => Some(ellipsis),
+    Overflow::Clip => None,
+    Overflow::WordWrap | Overflow::CharWrap => Some(Ellipsis::End),
+  };
+  let push_right_text = |mut line: PendingLine<'a>| {
+    line.push_spaces(
+      terminal_width.saturating_sub(line.char_width + right_width),
+    );
+    line.push_segment(right_text, right_width);
+    line
+  };
+
+  // the parts are separated by at least one space
+  let line = truncate_line(
+    text,
+    prefixes.first_line,
+    usize::MAX,
+    None,
+    options.tab_width,
+    options.width_profile,
+  );
+  if line.char_width + 1 + right_width <= terminal_width {
+    return vec![push_right_text(line)];
+  }
+  if layout.right_text_wraps {
+    // the right text goes on its own line, so every line of the text is
+    // marked as wrapped
+    let wrap_width = terminal_width - prefixes.end_marker.width;
+    let wrap_width = WrapWidth::new(text, wrap_width, wrap_width);
+    let mut lines = if layout.overflow == Overflow::CharWrap {
+      char_wrap_paragraph(
+        text,
+        prefixes.first_line,
+        prefixes.hanging,
+        wrap_width,
+        options,
+      )
+    } else {
+      wrap_paragraph(
+        text,
+        prefixes.first_line,
+        prefixes.hanging,
+        wrap_width,
+        options,
+      )
+    };
+    let mut right_line = truncate_line(
+      right_text,
+      LinePrefix::default(),
+      terminal_width,
+      ellipsis,
+      options.tab_width,
+      options.width_profile,
+    );
+    right_line.align(Align::Right, terminal_width);
+    lines.push(right_line);
+    return lines;
+  }
+  let available_width = terminal_width.saturating_sub(right_width + 1);
+  if available_width <= prefixes.first_line.width {
+    // the right text doesn't leave room for the text
+    return vec![truncate_line(
+      right_text,
+      prefixes.first_line,
+      terminal_width,
+      ellipsis,
+      options.tab_width,
+      options.width_profile,
+    )];
+  }
+  vec![push_right_text(truncate_line(
+    text,
+    prefixes.first_line,
+    available_width,
+    ellipsis,
+    options.tab_width,
+    options.width_profile,
+  ))]
+}
+
+// The width that the lines of a paragraph are wrapped at.
+#[derive(Clone, Copy)]
+struct WrapWidth<'a> {
+  paragraph: &'a str,
+  // leaves room for the end marker
+  width: usize,
+  last_line_width: usize,
+}
+
+impl<'a> WrapWidth<'a> {
+  fn new(paragraph: &'a str, width: usize, last_line_width: usize) -> Self {
+    Self {
+      paragraph,
+      width,
+      last_line_width,
+    }
+  }
+
+  // Gets whether a part of the paragraph starting at the column must move
+  // to the next line. It may use the room of the end marker when the rest
+  // of the paragraph fits, since that makes it the last line.
+  fn is_overflow(
+    &self,
+    part: &str,
+    part_width: usize,
+    column: usize,
+    options: &RenderOptions,
+  ) -> bool {
+    let end = column + part_width;
+    if end <= self.width {
+      return false;
+    }
+    if end > self.last_line_width {
+      return true;
+    }
+    let offset = part.as_ptr() as usize - self.paragraph.as_ptr() as usize;
+    !fits_in_columns(
+      &self.paragraph[offset..],
+      column,
+      self.last_line_width,
+      options,
+    )
+  }
+}
+
+// Gets whether the text starting at the column ends at or before the max
+// column, measuring only as much of the text as it takes to tell.
+fn fits_in_columns(
+  text: &str,
+  mut column: usize,
+  max_column: usize,
+  options: &RenderOptions,
+) -> bool {
+  let mut measure = |token: ansi::StreamToken| {
+    if token.is_escape {
+      column += ansi::movement_width(&token.text, column);
+    } else {
+      for grapheme in token.text.graphemes(true) {
+        column += if grapheme == "\t" {
+          tab_stop_width(column, options.tab_width)
+        } else {
+          options.width_profile.grapheme_width(grapheme)
+        };
+      }
+    }
+    column <= max_column
+  };
+  let mut tokenizer = ansi::StreamTokenizer::new();
+  for chunk in text.as_bytes().chunks(64) {
+    for token in tokenizer.push(chunk) {
+      if !measure(token) {
+        return false;
+      }
+    }
+  }
+  tokenizer.finish().is_none_or(measure)
+}
+
+fn wrap_paragraph<'a, L: WrappedLine<'a>>(
+  text: &'a str,
+  first_prefix: LinePrefix<'a>,
+  hanging_prefix: LinePrefix<'a>,
+  wrap_width: WrapWidth<'a>,
+  options: &RenderOptions,
+) -> Vec<L> {
+  let mut lines: Vec<L> = Vec::new();
+  let mut current_line = L::new(first_prefix);
+  let mut line_width: usize = first_prefix.width;
   let mut pending_whitespace: Option<&'a str> = None;
+  let mut pending_soft_hyphen = false;
 
   for token in tokenize_words(text) {
     match token {
       WordToken::Word(word) => {
-        let word_width =
-          UnicodeWidthStr::width(strip_ansi_codes(word).as_ref());
+        let is_after_soft_hyphen = std::mem::take(&mut pending_soft_hyphen);
+        let mut word_width =
+          text_width_at(word, line_width, options.width_profile);
         let is_word_longer_than_half_line =
-          hanging_indent + word_width > (terminal_width / 2);
+          hanging_prefix.width + word_width > (wrap_width.width / 2);
         if is_word_longer_than_half_line {
           // flush pending whitespace if it still fits on the line
           if let Some(ws) = pending_whitespace.take()
-            && line_width < terminal_width
+            && line_width < wrap_width.width
           {
-            let ws_width = visible_whitespace_width(ws);
-            current_line.push_segment(ws, ws_width);
-          }
-          // break the word at char boundaries across multiple lines,
-          // preserving ANSI escapes as zero-width segments
-          for ansi_token in ansi::tokenize(word) {
-            let chunk = &word[ansi_token.range.clone()];
-            if ansi_token.is_escape {
-              current_line.push_segment(chunk, 0);
-              continue;
-            }
-            let mut seg_start = 0;
-            let mut seg_width = 0;
-            let mut byte_pos = 0;
-            for c in chunk.chars() {
-              let c_len = c.len_utf8();
-              if let Some(char_width) =
-                unicode_width::UnicodeWidthChar::width(c)
-              {
-                if line_width + char_width > terminal_width {
-                  if byte_pos > seg_start {
-                    current_line
-                      .push_segment(&chunk[seg_start..byte_pos], seg_width);
-                  }
-                  lines.push(std::mem::replace(
-                    &mut current_line,
-                    PendingLine::new(hanging_indent),
-                  ));
-                  line_width = hanging_indent;
-                  seg_start = byte_pos;
-                  seg_width = 0;
-                }
-                line_width += char_width;
-                seg_width += char_width;
-              }
-              byte_pos += c_len;
-            }
-            if byte_pos > seg_start {
-              current_line.push_segment(&chunk[seg_start..byte_pos], seg_width);
-            }
+            current_line.push_expanding_tabs(ws, options.tab_width, |s| {
+              visible_whitespace_width(s, options.width_profile)
+            });
           }
+          push_char_wrapped(
+            word,
+            &mut lines,
+            &mut current_line,
+            &mut line_width,
+            hanging_prefix,
+            wrap_width,
+            options,
+          );
         } else {
-          if line_width + word_width > terminal_width {
+          if wrap_width.is_overflow(word, word_width, line_width, options) {
+            // a soft hyphen is only displayed when breaking at it
+            if is_after_soft_hyphen && line_width < wrap_width.width {
+              current_line.push_segment("-", 1);
+            }
             lines.push(std::mem::replace(
               &mut current_line,
-              PendingLine::new(hanging_indent),
+              L::new(hanging_prefix),
             ));
-            line_width = hanging_indent;
+            line_width = hanging_prefix.width;
             pending_whitespace = None;
+            word_width = text_width_at(word, line_width, options.width_profile);
           }
           if let Some(ws) = pending_whitespace.take() {
-            let ws_width = visible_whitespace_width(ws);
-            current_line.push_segment(ws, ws_width);
+            current_line.push_expanding_tabs(ws, options.tab_width, |s| {
+              visible_whitespace_width(s, options.width_profile)
+            });
           }
           current_line.push_segment(word, word_width);
           line_width += word_width;
         }
       }
       WordToken::WhiteSpace(ws) => {
+        pending_soft_hyphen = false;
         pending_whitespace = Some(ws);
-        line_width += visible_whitespace_width(ws);
+        for (i, piece) in ws.split('\t').enumerate() {
+          if i > 0 {
+            line_width += tab_stop_width(line_width, options.tab_width);
+          }
+          line_width += visible_whitespace_width(piece, options.width_profile);
+        }
+      }
+      WordToken::SoftHyphen => {
+        pending_soft_hyphen = true;
       }
       WordToken::LfNewLine | WordToken::CrlfNewLine => {
         // the caller splits on '\n' before invoking this function, so
@@ -615,12 +2041,126 @@ fn wrap_paragraph<'a>(
   lines
 }
 
-fn visible_whitespace_width(s: &str) -> usize {
+fn char_wrap_paragraph<'a, L: WrappedLine<'a>>(
+  text: &'a str,
+  first_prefix: LinePrefix<'a>,
+  hanging_prefix: LinePrefix<'a>,
+  wrap_width: WrapWidth<'a>,
+  options: &RenderOptions,
+) -> Vec<L> {
+  let mut lines: Vec<L> = Vec::new();
+  let mut current_line = L::new(first_prefix);
+  let mut line_width: usize = first_prefix.width;
+  push_char_wrapped(
+    text,
+    &mut lines,
+    &mut current_line,
+    &mut line_width,
+    hanging_prefix,
+    wrap_width,
+    options,
+  );
+  if current_line.has_content() {
+    lines.push(current_line);
+  }
+  lines
+}
+
+// Breaks the text at grapheme cluster boundaries across multiple lines,
+// preserving ANSI escapes as zero-width segments.
+fn push_char_wrapped<'a, L: WrappedLine<'a>>(
+  text: &'a str,
+  lines: &mut Vec<L>,
+  current_line: &mut L,
+  line_width: &mut usize,
+  hanging_prefix: LinePrefix<'a>,
+  wrap_width: WrapWidth<'a>,
+  options: &RenderOptions,
+) {
+  for ansi_token in ansi::tokenize(text) {
+    let chunk = &text[ansi_token.range.clone()];
+    if ansi_token.is_escape {
+      // the terminal stops moving the cursor at the right edge
+      let width = ansi::movement_width(chunk, *line_width)
+        .min(wrap_width.last_line_width.saturating_sub(*line_width));
+      current_line.push_segment(chunk, width);
+      *line_width += width;
+      continue;
+    }
+    let mut seg_start = 0;
+    let mut seg_width = 0;
+    for (byte_pos, grapheme) in chunk.grapheme_indices(true) {
+      let is_tab = grapheme == "\t";
+      let grapheme_width = if is_tab {
+        tab_stop_width(*line_width, options.tab_width)
+      } else {
+        options.width_profile.grapheme_width(grapheme)
+      };
+      let is_line_break =
+        wrap_width.is_overflow(grapheme, grapheme_width, *line_width, options);
+      if is_line_break || is_tab {
+        if byte_pos > seg_start {
+          current_line.push_segment(&chunk[seg_start..byte_pos], seg_width);
+        }
+        seg_start = byte_pos;
+        seg_width = 0;
+      }
+      if is_line_break {
+        lines.push(std::mem::replace(current_line, L::new(hanging_prefix)));
+        *line_width = hanging_prefix.width;
+      }
+      if is_tab {
+        // tabs are expanded to spaces, but dropped at a line break
+        // like other whitespace
+        if !is_line_break {
+          current_line.push_spaces(grapheme_width);
+          *line_width += grapheme_width;
+        }
+        seg_start = byte_pos + grapheme.len();
+        continue;
+      }
+      *line_width += grapheme_width;
+      seg_width += grapheme_width;
+    }
+    if chunk.len() > seg_start {
+      current_line.push_segment(&chunk[seg_start..], seg_width);
+    }
+  }
+}
+
+// Measures text that starts at the column, which matters for escape
+// sequences that move the cursor to an absolute column (ex. `ESC[20G`).
+fn text_width_at(
+  text: &str,
+  column: usize,
+  width_profile: WidthProfile,
+) -> usize {
+  if !text.contains('\x1b') {
+    return width_profile.str_width(text);
+  }
+  let mut end = column;
+  for token in ansi::tokenize(text) {
+    let token_text = &text[token.range];
+    end += if token.is_escape {
+      ansi::movement_width(token_text, end)
+    } else {
+      width_profile.str_width(token_text)
+    };
+  }
+  end - column
+}
+
+fn visible_whitespace_width(s: &str, width_profile: WidthProfile) -> usize {
   s.chars()
-    .map(|c| unicode_width::UnicodeWidthChar::width(c).unwrap_or(1))
+    .map(|c| width_profile.char_width(c).unwrap_or(1))
     .sum()
 }
 
+/// Gets the number of columns a tab at the provided column advances.
+fn tab_stop_width(column: usize, tab_width: usize) -> usize {
+  tab_width - column % tab_width
+}
+
 fn are_collections_equal<T: PartialEq>(a: &[T], b: &[T]) -> bool {
   a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| a == b)
 }
@@ -630,12 +2170,26 @@ mod test {
   use std::sync::Arc;
   use std::sync::Mutex;
 
+  use crate::Align;
+  use crate::Anchor;
   use crate::ConsoleSize;
   use crate::ConsoleStaticText;
+  use crate::Ellipsis;
+  use crate::FullWidthLines;
+  use crate::HiddenLinesSummary;
+  use crate::MaxHeight;
+  use crate::Overflow;
+  use crate::Reflow;
+  use crate::Span;
+  use crate::Style;
+  use crate::StyledLine;
   use crate::TextItem;
   use crate::VTS_CLEAR_CURSOR_DOWN;
   use crate::VTS_CLEAR_UNTIL_NEWLINE;
   use crate::VTS_MOVE_TO_ZERO_COL;
+  use crate::WrapMarkers;
+  use crate::ansi::Color;
+  use crate::ansi::SanitizeMode;
   use crate::vts_move_down;
   use crate::vts_move_up;
 
@@ -702,6 +2256,24 @@ mod test {
         .map(|text| self.map_text_from(&text))
     }
 
+    pub fn render_items(&mut self, items: &[TextItem]) -> Option<String> {
+      self
+        .inner
+        .render_items(items.iter())
+        .map(|text| self.map_text_from(&text))
+    }
+
+    pub fn render_items_with_anchor(
+      &mut self,
+      items: &[TextItem],
+      anchor: Anchor,
+    ) -> Option<String> {
+      self
+        .inner
+        .render_items_with_anchor(items.iter(), anchor)
+        .map(|text| self.map_text_from(&text))
+    }
+
     pub fn render_clear(&mut self) -> Option<String> {
       self
         .inner
@@ -772,6 +2344,574 @@ mod test {
     );
   }
 
+  #[test]
+  fn wraps_at_grapheme_cluster_boundaries() {
+    let mut tester = Tester::new();
+    tester.set_cols(Some(5));
+    // a family emoji is one two column wide cluster made of five chars
+    let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+    let result = tester.render(&family.repeat(3)).unwrap();
+    assert_eq!(
+      result,
+      format!("~MOVE0~~CLEAR_CDOWN~{0}{0}\r\n{0}~MOVE0~", family)
+    );
+    tester.set_rows(Some(1));
+    let result = tester.render(&format!("e\u{301}{}", family)).unwrap();
+    assert_eq!(
+      result,
+      format!("~MOVE0~~CLEAR_CDOWN~e\u{301}{}~MOVE0~", family)
+    );
+  }
+
+  #[test]
+  fn wraps_at_line_break_opportunities() {
+    let mut tester = Tester::new();
+    // non-breaking spaces keep the words together
+    let result = tester.render("abcde 1\u{A0}MiB").unwrap();
+    assert_eq!(result, "~MOVE0~~CLEAR_CDOWN~abcde\r\n1\u{A0}MiB~MOVE0~");
+    // soft hyphens are only shown when breaking at them
+    let result = tester.render("hyphen\u{AD}ation").unwrap();
+    assert_eq!(result, "~MOVE0~~CUP1~hyphen-\r\nation~MOVE0~");
+    let result = tester.render("hyphen\u{AD}ate").unwrap();
+    assert_eq!(
+      result,
+      "~MOVE0~~CUP1~hyphenate~CDOWN1~~CLEAR_CDOWN~~CUP1~~MOVE0~"
+    );
+    // cjk text breaks between ideographs
+    let result = tester.render("你好世界你好").unwrap();
+    assert_eq!(result, "~MOVE0~你好世界你\r\n好~MOVE0~");
+  }
+
+  #[test]
+  fn expands_tabs_to_tab_stops() {
+    let mut tester = Tester::new();
+    let result = tester.render("a\tb\n\tc").unwrap();
+    assert_eq!(result, "~MOVE0~~CLEAR_CDOWN~a       b\r\n        c~MOVE0~");
+
+    // tab stops are relative to the start of the line, including the indent
+    let size = ConsoleSize {
+      cols: Some(20),
+      rows: Some(10),
+    };
+    let mut s = ConsoleStaticText::new(move || size);
+    s.tab_width(4);
+    let items = [TextItem::with_hanging_indent(
+      "aaaaaaa bbbbbbb ccccc d\te",
+      3,
+    )];
+    let result = s.render_items_with_size(items.iter(), size).unwrap();
+    assert!(result.contains("aaaaaaa bbbbbbb\r\n   ccccc d  e"));
+  }
+
+  #[test]
+  fn measures_ambiguous_width_with_profile() {
+    let mut tester = Tester::new();
+    let result = tester.render("○○○○○○").unwrap();
+    assert_eq!(result, "~MOVE0~~CLEAR_CDOWN~○○○○○○~MOVE0~");
+
+    let mut tester = Tester::new();
+    tester.inner.width_profile(crate::WidthProfile::Wide);
+    let result = tester.render("○○○○○○").unwrap();
+    assert_eq!(result, "~MOVE0~~CLEAR_CDOWN~○○○○○\r\n○~MOVE0~");
+    let result = tester.render("○○○○").unwrap();
+    assert_eq!(
+      result,
+      "~MOVE0~~CUP1~○○○○~CLEAR_UNTIL_NEWLINE~~CDOWN1~~CLEAR_CDOWN~~CUP1~~MOVE0~"
+    );
+  }
+
+  #[test]
+  fn renders_item_overflow() {
+    let text = "hello world foo\nbar";
+    let cases = [
+      (Overflow::CharWrap, "hello worl\r\nd foo\r\nbar"),
+      (Overflow::Truncate(Ellipsis::End), "hello wor…\r\nbar"),
+      (Overflow::Truncate(Ellipsis::Start), "…world foo\r\nbar"),
+      (Overflow::Truncate(Ellipsis::Middle), "hello… foo\r\nbar"),
+      (Overflow::Clip, "hello worl\r\nbar"),
+    ];
+    for (overflow, expected) in cases {
+      let mut tester = Tester::new();
+      let result = tester
+        .render_items(&[TextItem::new(text).with_overflow(overflow)])
+        .unwrap();
+      assert_eq!(result, format!("~MOVE0~~CLEAR_CDOWN~{}~MOVE0~", expected));
+    }
+
+    // escape sequences in the truncated text are kept
+    let mut tester = Tester::new();
+    let result = tester
+      .render_items(&[TextItem::new("\x1b[31mhello world\x1b[0m foo")
+        .with_overflow(Overflow::Truncate(Ellipsis::End))])
+      .unwrap();
+    assert_eq!(
+      result,
+      "~MOVE0~~CLEAR_CDOWN~\x1b[31mhello wor…\x1b[0m~MOVE0~"
+    );
+  }
+
+  #[test]
+  fn carries_styles_across_wrapped_lines() {
+    let mut tester = Tester::new();
+    let result = tester.render("\x1b[31mhello world foo\x1b[0m").unwrap();
+    assert_eq!(
+      result,
+      concat!(
+        "~MOVE0~~CLEAR_CDOWN~\x1b[31mhello\x1b[0m\r\n",
+        "\x1b[31mworld foo\x1b[0m~MOVE0~",
+      )
+    );
+
+    // the style is restored when the lines above are truncated away
+    let mut tester = Tester::new();
+    tester.set_rows(Some(1));
+    let result = tester.render("\x1b[1;32mhello\nworld\x1b[22m foo").unwrap();
+    assert_eq!(
+      result,
+      "~MOVE0~~CLEAR_CDOWN~\x1b[1;32mworld\x1b[22m foo\x1b[0m~MOVE0~"
+    );
+  }
+
+  #[test]
+  fn renders_prefixed_text() {
+    let mut tester = Tester::new();
+    tester.set_cols(Some(14));
+    let result = tester
+      .render_items(&[TextItem::with_prefix(
+        "hello world foo bar baz\nqux",
+        "\x1b[2m│\x1b[0m ",
+      )
+      .with_first_line_prefix("├ ")])
+      .unwrap();
+    assert_eq!(
+      result,
+      concat!(
+        "~MOVE0~~CLEAR_CDOWN~├ hello world\r\n",
+        "\x1b[2m│\x1b[0m foo bar baz\r\n",
+        "\x1b[2m│\x1b[0m qux~MOVE0~",
+      )
+    );
+
+    // a prefix's unclosed style doesn't leak into the text
+    let mut tester = Tester::new();
+    let result = tester
+      .render_items(&[TextItem::with_prefix("a b", "\x1b[2m> ")])
+      .unwrap();
+    assert_eq!(result, "~MOVE0~~CLEAR_CDOWN~\x1b[2m> \x1b[0ma b~MOVE0~");
+
+    // a prefix that leaves no room for the text is dropped
+    let mut tester = Tester::new();
+    tester.set_cols(Some(2));
+    let result = tester
+      .render_items(&[TextItem::with_prefix("abc", "│ ")])
+      .unwrap();
+    assert_eq!(result, "~MOVE0~~CLEAR_CDOWN~ab\r\nc~MOVE0~");
+
+    // a first line prefix without a prefix only applies to the first line
+    let mut tester = Tester::new();
+    let result = tester
+      .render_items(&[TextItem::with_hanging_indent("aaa bbb ccc", 2)
+        .with_first_line_prefix("- ")])
+      .unwrap();
+    assert_eq!(result, "~MOVE0~~CLEAR_CDOWN~- aaa bbb\r\n  ccc~MOVE0~");
+  }
+
+  #[test]
+  fn renders_wrap_markers() {
+    let mut tester = Tester::new();
+    tester.inner.wrap_markers(WrapMarkers {
+      end: Some("↩".into()),
+      start: Some("\x1b[2m↪".into()),
+    });
+    let result = tester
+      .render_items(&[
+        TextItem::new("aaa bbb ccc\nddd"),
+        TextItem::new("abcdefghijkl").with_overflow(Overflow::CharWrap),
+      ])
+      .unwrap();
+    assert_eq!(
+      result,
+      concat!(
+        "~MOVE0~~CLEAR_CDOWN~aaa bbb↩\r\n",
+        "\x1b[2m↪\x1b[0mccc\r\n",
+        "ddd\r\n",
+        "abcdefghi↩\r\n",
+        "\x1b[2m↪\x1b[0mjkl~MOVE0~",
+      )
+    );
+    // the last line of a paragraph has no marker, so it uses the full width
+    tester.render_clear();
+    let result = tester
+      .render_items(&[
+        TextItem::new("aaa bbbbbb"),
+        TextItem::new("abcdefghij").with_overflow(Overflow::CharWrap),
+      ])
+      .unwrap();
+    assert_eq!(result, "~MOVE0~aaa bbbbbb\r\nabcdefghij~MOVE0~",);
+    // a prefix one column narrower than the console leaves no room for
+    // the end marker
+    tester.render_clear();
+    let result = tester
+      .render_items(&[
+        TextItem::with_hanging_indent("aaaaaaaaaa b", 9),
+        TextItem::with_prefix("abcdefgh", "123456789")
+          .with_overflow(Overflow::CharWrap),
+      ])
+      .unwrap();
+    assert_eq!(
+      result,
+      concat!(
+        "~MOVE0~aaaaaaaaaa\r\n",
+        "         b\r\n",
+        "123456789a\r\n",
+        "123456789b\r\n",
+        "123456789c\r\n",
+        "123456789d\r\n",
+        "123456789e\r\n",
+        "123456789f\r\n",
+        "123456789g\r\n",
+        "123456789h~MOVE0~",
+      )
+    );
+  }
+
+  #[test]
+  fn renders_aligned_text() {
+    let mut tester = Tester::new();
+    let result = tester
+      .render_items(&[
+        TextItem::new("12 MiB").with_align(Align::Right),
+        TextItem::new("aaa bbb ccc").with_align(Align::Center),
+        TextItem::new("你好").with_align(Align::Right),
+      ])
+      .unwrap();
+    assert_eq!(
+      result,
+      concat!(
+        "~MOVE0~~CLEAR_CDOWN~    12 MiB\r\n",
+        " aaa bbb\r\n",
+        "   ccc\r\n",
+        "      你好~MOVE0~",
+      )
+    );
+  }
+
+  #[test]
+  fn renders_right_text() {
+    let mut tester = Tester::new();
+    tester.set_cols(Some(20));
+    let result = tester
+      .render_items(&[
+        TextItem::new("a").with_right_text("b"),
+        TextItem::new("你好你好你好你好").with_right_text("1/2"),
+        TextItem::new("Downloading foo").with_right_text("3 MiB"),
+        TextItem::new("Downloading foo")
+          .with_right_text("3 MiB")
+          .with_overflow(Overflow::Truncate(Ellipsis::Start)),
+      ])
+      .unwrap();
+    assert_eq!(
+      result,
+      concat!(
+        "~MOVE0~~CLEAR_CDOWN~a                  b\r\n",
+        "你好你好你好你好 1/2\r\n",
+        "Downloading f… 3 MiB\r\n",
+        "…wnloading foo 3 MiB~MOVE0~",
+      )
+    );
+
+    let mut tester = Tester::new();
+    let result = tester
+      .render_items(&[TextItem::new("aaa bbb ccc")
+        .with_right_text("9 MiB")
+        .with_right_text_wrapping(true)])
+      .unwrap();
+    assert_eq!(
+      result,
+      "~MOVE0~~CLEAR_CDOWN~aaa bbb\r\nccc\r\n     9 MiB~MOVE0~",
+    );
+  }
+
+  #[test]
+  fn renders_max_height() {
+    let mut tester = Tester::new();
+    tester.set_cols(Some(20));
+    let result = tester
+      .render_items(&[
+        TextItem::new("1\n2\n3\n4\n5").with_max_height(MaxHeight::Last(2)),
+        TextItem::new("a b c").with_max_height(MaxHeight::Last(2)),
+        TextItem::new("1\n2\n3").with_max_height(MaxHeight::First(2)),
+      ])
+      .unwrap();
+    assert_eq!(
+      result,
+      concat!(
+        "~MOVE0~~CLEAR_CDOWN~\x1b[2m… 3 more lines\x1b[0m\r\n",
+        "4\r\n",
+        "5\r\n",
+        "a b c\r\n",
+        "1\r\n",
+        "2\r\n",
+        "\x1b[2m… 1 more line\x1b[0m~MOVE0~",
+      )
+    );
+
+    let mut tester = Tester::new();
+    tester.set_cols(Some(4));
+    tester.set_rows(Some(2));
+    tester.inner.hidden_lines_summary(HiddenLinesSummary {
+      style: Style::new(),
+      format: |count| format!("+{}", count),
+    });
+    let result = tester
+      .render_items(&[TextItem::new("aaa bbb ccc ddd eee")
+        .with_max_height(MaxHeight::First(3))])
+      .unwrap();
+    assert_eq!(result, "~MOVE0~~CLEAR_CDOWN~ccc\r\n+2~MOVE0~");
+  }
+
+  #[test]
+  fn renders_with_anchor() {
+    let items = [
+      TextItem::new("a"),
+      TextItem::new("b"),
+      TextItem::new("c\nd"),
+      TextItem::new("e"),
+      TextItem::new("f"),
+    ];
+    let render = |anchor: Anchor| {
+      let mut tester = Tester::new();
+      tester.set_rows(Some(3));
+      tester.render_items_with_anchor(&items, anchor).unwrap()
+    };
+    assert_eq!(
+      render(Anchor::Bottom),
+      "~MOVE0~~CLEAR_CDOWN~d\r\ne\r\nf~MOVE0~"
+    );
+    assert_eq!(
+      render(Anchor::Top),
+      "~MOVE0~~CLEAR_CDOWN~a\r\nb\r\nc~MOVE0~"
+    );
+    assert_eq!(
+      render(Anchor::Item(2)),
+      "~MOVE0~~CLEAR_CDOWN~c\r\nd\r\ne~MOVE0~"
+    );
+    assert_eq!(
+      render(Anchor::Item(1)),
+      "~MOVE0~~CLEAR_CDOWN~b\r\nc\r\nd~MOVE0~"
+    );
+    assert_eq!(
+      render(Anchor::Item(9)),
+      "~MOVE0~~CLEAR_CDOWN~d\r\ne\r\nf~MOVE0~"
+    );
+  }
+
+  #[test]
+  fn renders_responsive_items() {
+    let items = [TextItem::Responsive(vec![
+      (16, TextItem::new("[###>--] 45% 3s")),
+      (6, TextItem::new("45% 3s")),
+      (3, TextItem::new("45%")),
+    ])
+    .with_align(Align::Right)];
+    let render = |cols: u16| {
+      let mut tester = Tester::new();
+      tester.set_cols(Some(cols));
+      tester.render_items(&items).unwrap()
+    };
+    assert_eq!(
+      render(20),
+      "~MOVE0~~CLEAR_CDOWN~     [###>--] 45% 3s~MOVE0~"
+    );
+    assert_eq!(render(8), "~MOVE0~~CLEAR_CDOWN~  45% 3s~MOVE0~");
+    assert_eq!(render(2), "~MOVE0~~CLEAR_CDOWN~45\r\n %~MOVE0~");
+  }
+
+  #[test]
+  fn renders_too_small_placeholder() {
+    let mut tester = Tester::new();
+    tester.inner.min_size(ConsoleSize {
+      cols: Some(8),
+      rows: None,
+    });
+    tester.inner.too_small_placeholder("widen me please");
+    tester.set_cols(Some(6));
+    let result = tester.render("one two three").unwrap();
+    assert_eq!(result, "~MOVE0~~CLEAR_CDOWN~widen…~MOVE0~");
+    // the placeholder is cleared when the console grows back
+    tester.set_cols(Some(8));
+    let result = tester.render("one two three").unwrap();
+    assert_eq!(result, "~MOVE0~~CLEAR_CDOWN~one two\r\nthree~MOVE0~");
+  }
+
+  #[test]
+  fn models_reflow_after_resize() {
+    let render = |reflow: Reflow| {
+      let mut tester = Tester::new();
+      tester.inner.reflow(reflow);
+      tester.render("aaaaaaaaaa\nbbbbbbbbbb").unwrap();
+      tester.set_cols(Some(5));
+      tester.render("aaaaaaaaaa\nbbbbbbbbbb").unwrap()
+    };
+    // the cursor stays on the first row of the rewrapped last line
+    assert_eq!(
+      render(Reflow::Rewrap),
+      "~MOVE0~~CUP2~~CLEAR_CDOWN~aaaaa\r\naaaaa\r\nbbbbb\r\nbbbbb~MOVE0~",
+    );
+    assert_eq!(
+      render(Reflow::None),
+      "~MOVE0~~CUP1~~CLEAR_CDOWN~aaaaa\r\naaaaa\r\nbbbbb\r\nbbbbb~MOVE0~",
+    );
+    // the cursor is assumed to be on the last row
+    let mut tester = Tester::new();
+    tester.render("aaaaaaaaaa\nbbbbbbbbbb").unwrap();
+    tester.set_cols(Some(5));
+    assert_eq!(
+      tester.render("c").unwrap(),
+      "~MOVE0~~CUP3~~CLEAR_CDOWN~c~CDOWN1~~CLEAR_CDOWN~~CUP1~~MOVE0~",
+    );
+    // nothing of the last text is displayed on a console without rows, or
+    // without columns when lines are cut off
+    for (reflow, rows, cols, expected) in [
+      (
+        Reflow::Rewrap,
+        Some(0),
+        Some(5),
+        "~MOVE0~~CLEAR_CDOWN~~MOVE0~",
+      ),
+      (
+        Reflow::None,
+        Some(10),
+        Some(0),
+        "~MOVE0~~CLEAR_CDOWN~\r\nc~MOVE0~",
+      ),
+    ] {
+      let mut tester = Tester::new();
+      tester.inner.reflow(reflow);
+      tester.render("aaaaaaaaaa\nbbbbbbbbbb").unwrap();
+      tester.set_rows(rows);
+      tester.set_cols(cols);
+      assert_eq!(tester.render("c").unwrap(), expected);
+    }
+  }
+
+  #[test]
+  fn handles_full_width_lines() {
+    let render = |full_width_lines: FullWidthLines| {
+      let mut tester = Tester::new();
+      tester.inner.full_width_lines(full_width_lines);
+      tester.render("0123456789abc").unwrap()
+    };
+    assert_eq!(
+      render(FullWidthLines::Write),
+      "~MOVE0~~CLEAR_CDOWN~0123456789\r\nabc~MOVE0~"
+    );
+    assert_eq!(
+      render(FullWidthLines::ClearPendingWrap),
+      "~MOVE0~~CLEAR_CDOWN~0123456789~MOVE0~\r\nabc~MOVE0~"
+    );
+    assert_eq!(
+      render(FullWidthLines::ReserveLastColumn),
+      "~MOVE0~~CLEAR_CDOWN~012345678\r\n9abc~MOVE0~"
+    );
+  }
+
+  #[test]
+  fn renders_applied_overwrites() {
+    let mut tester = Tester::new();
+    let result = tester
+      .render_items(&[
+        TextItem::new("10%\r100%\nab\x08c").with_overwrites_applied(),
+        TextItem::with_hanging_indent("a\rb", 2).with_overwrites_applied(),
+      ])
+      .unwrap();
+    assert_eq!(result, "~MOVE0~~CLEAR_CDOWN~100%\r\nac\r\nb~MOVE0~");
+    // wide characters are measured with the console's width profile
+    let mut tester = Tester::new();
+    tester.inner.width_profile(crate::WidthProfile::Wide);
+    let result = tester
+      .render_items(&[TextItem::new("○○\rx").with_overwrites_applied()])
+      .unwrap();
+    assert_eq!(result, "~MOVE0~~CLEAR_CDOWN~x ○~MOVE0~");
+  }
+
+  #[test]
+  fn renders_sanitized_text() {
+    let mut tester = Tester::new();
+    let result = tester
+      .render_items(&[TextItem::new("\x1b[2J\x1b[31mab\x07\x1b[0m")
+        .with_sanitized_text(SanitizeMode::Remove)])
+      .unwrap();
+    assert_eq!(result, "~MOVE0~~CLEAR_CDOWN~\x1b[31mab^G\x1b[0m~MOVE0~");
+  }
+
+  #[test]
+  fn wraps_horizontal_movements() {
+    let mut tester = Tester::new();
+    let result = tester.render("a\x1b[5Cb cdef").unwrap();
+    assert_eq!(result, "~MOVE0~~CLEAR_CDOWN~a\x1b[5Cb\r\ncdef~MOVE0~");
+    assert_eq!(
+      super::Line::new(
+        "ab\x1b[C\x1b[3X\x1b[10G".to_string(),
+        super::WidthProfile::Narrow
+      )
+      .char_width,
+      9
+    );
+    assert_eq!(
+      super::Line::new("a\x1b[3Xb".to_string(), super::WidthProfile::Narrow)
+        .char_width,
+      2
+    );
+    // an absolute column is measured from where the word starts
+    let mut tester = Tester::new();
+    let result = tester.render("ab \x1b[8Gc").unwrap();
+    assert_eq!(result, "~MOVE0~~CLEAR_CDOWN~ab \x1b[8Gc~MOVE0~");
+    // the cursor stops moving at the right edge
+    let items =
+      [TextItem::new("ab\x1b[20Ccd").with_overflow(Overflow::CharWrap)];
+    let lines = super::render_items(
+      items.iter(),
+      ConsoleSize {
+        cols: Some(10),
+        rows: None,
+      },
+      Anchor::Bottom,
+      &Default::default(),
+    );
+    assert_eq!(
+      lines
+        .iter()
+        .map(|line| (line.text.as_str(), line.char_width))
+        .collect::<Vec<_>>(),
+      vec![("ab\x1b[20C", 10), ("cd", 2)]
+    );
+  }
+
+  #[test]
+  fn renders_styled_lines() {
+    let line = StyledLine::new()
+      .with_span(Span::new("bold").with_style(Style::new().bold()))
+      .with_span(Span::new(" "))
+      .with_span(
+        Span::new("green text").with_style(Style::new().fg(Color::Ansi(2))),
+      );
+    let items = [TextItem::from(line)];
+    let mut tester = Tester::new();
+    let result = tester.render_items(&items).unwrap();
+    assert_eq!(
+      result,
+      concat!(
+        "~MOVE0~~CLEAR_CDOWN~\x1b[1mbold\x1b[0m \x1b[32mgreen\x1b[0m\r\n",
+        "\x1b[32mtext\x1b[0m~MOVE0~",
+      )
+    );
+    let mut tester = Tester::new();
+    tester.inner.color(false);
+    let result = tester.render_items(&items).unwrap();
+    assert_eq!(result, "~MOVE0~~CLEAR_CDOWN~bold green\r\ntext~MOVE0~");
+  }
+
   // Lots of text items must only render the bottom ones that fit on
   // screen — see https://github.com/dsherret/console_static_text/issues/1
   #[test]
@@ -0,0 +1,614 @@
+use std::borrow::Cow;
+
+use crate::Span;
+use crate::Style;
+use crate::StyledLine;
+use crate::ansi;
+use crate::ansi::Color;
+use crate::ansi::SanitizeMode;
+
+const COLOR_NAMES: [&str; 8] = [
+  "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// An error in markup, with the byte index of where it occurs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarkupError {
+  pub kind: MarkupErrorKind,
+  pub index: usize,
+}
+
+impl std::fmt::Display for MarkupError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} at byte {}", self.kind.message(), self.index)
+  }
+}
+
+impl std::error::Error for MarkupError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkupErrorKind {
+  /// A `[` without a matching `]`.
+  UnterminatedTag,
+  /// A word in a tag that isn't a style, color, or link.
+  UnknownStyle,
+  /// A `[/]` without an open tag.
+  UnexpectedClosingTag,
+  /// A closing tag with text after the `/` (ex. `[/bold]`).
+  InvalidClosingTag,
+  /// A link with brackets in an unquoted url, or a quoted url with text
+  /// after its closing quote.
+  InvalidLink,
+  /// An open tag without a matching `[/]`.
+  UnclosedTag,
+}
+
+impl MarkupErrorKind {
+  pub const fn message(self) -> &'static str {
+    match self {
+      MarkupErrorKind::UnterminatedTag => "tag is missing its closing bracket",
+      MarkupErrorKind::UnknownStyle => "unknown style in tag",
+      MarkupErrorKind::UnexpectedClosingTag => {
+        "closing tag without an open tag"
+      }
+      MarkupErrorKind::InvalidClosingTag => "closing tag must be `[/]`",
+      MarkupErrorKind::InvalidLink => {
+        "link url must end the tag and be quoted when it has brackets"
+      }
+      MarkupErrorKind::UnclosedTag => "tag is never closed",
+    }
+  }
+}
+
+// A piece of markup, where the ranges are byte indexes.
+#[derive(Clone, Copy)]
+enum Token {
+  Text(usize, usize),
+  Open {
+    style: Style,
+    link: Option<(usize, usize)>,
+  },
+  Close,
+}
+
+/// Parses markup into styled text (ex. `"[bold green]✔[/] Finished"`).
+///
+/// A tag contains space separated styles, which are `bold`, `dim`,
+/// `italic`, `underline`, `inverse`, colors, background colors prefixed
+/// with `on_` (ex. `on_blue`), and `link=<url>`, where a url containing
+/// brackets is quoted (ex. `link="http://[::1]/"`). Colors are the names of
+/// the 8 standard colors with an optional `bright_` prefix, a 256 color
+/// palette index (ex. `208`), or a hex color (ex. `#ff8700`). A tag
+/// applies until the next `[/]`, on top of any tags it's nested in. A
+/// literal `[` is written as `[[` (see `escape`).
+pub fn parse(text: &str) -> Result<StyledLine<'_>, MarkupError> {
+  let bytes = text.as_bytes();
+  let mut line = StyledLine::new();
+  let mut open_tags: Vec<(usize, Style, Option<&str>)> = Vec::new();
+  let mut index = 0;
+  while index < bytes.len() {
+    let (token, next_index) = next_token(bytes, index)?;
+    match token {
+      Token::Text(start, end) => {
+        let mut span = Span::new(&text[start..end]);
+        if let Some((_, style, link)) = open_tags.last() {
+          span.style = *style;
+          span.link = link.map(Cow::Borrowed);
+        }
+        line.push(span);
+      }
+      Token::Open { style, link } => {
+        let (parent_style, parent_link) = match open_tags.last() {
+          Some((_, style, link)) => (*style, *link),
+          None => (Style::new(), None),
+        };
+        let link = link.map(|(start, end)| &text[start..end]).or(parent_link);
+        open_tags.push((index, parent_style.patch(style), link));
+      }
+      Token::Close => {
+        if open_tags.pop().is_none() {
+          return Err(MarkupError {
+            kind: MarkupErrorKind::UnexpectedClosingTag,
+            index,
+          });
+        }
+      }
+    }
+    index = next_index;
+  }
+  match open_tags.first() {
+    Some((index, _, _)) => Err(MarkupError {
+      kind: MarkupErrorKind::UnclosedTag,
+      index: *index,
+    }),
+    None => Ok(line),
+  }
+}
+
+/// Checks that the markup is valid, which can be done at compile time
+/// (see the `markup!` macro).
+pub const fn validate(text: &str) -> Result<(), MarkupError> {
+  let bytes = text.as_bytes();
+  let mut depth = 0;
+  // the outermost tag that's open, which is the one reported when it's
+  // never closed
+  let mut outer_tag_index = 0;
+  let mut index = 0;
+  while index < bytes.len() {
+    let (token, next_index) = match next_token(bytes, index) {
+      Ok(result) => result,
+      Err(err) => return Err(err),
+    };
+    match token {
+      Token::Text(..) => {}
+      Token::Open { .. } => {
+        if depth == 0 {
+          outer_tag_index = index;
+        }
+        depth += 1;
+      }
+      Token::Close => {
+        if depth == 0 {
+          return Err(MarkupError {
+            kind: MarkupErrorKind::UnexpectedClosingTag,
+            index,
+          });
+        }
+        depth -= 1;
+      }
+    }
+    index = next_index;
+  }
+  if depth > 0 {
+    Err(MarkupError {
+      kind: MarkupErrorKind::UnclosedTag,
+      index: outer_tag_index,
+    })
+  } else {
+    Ok(())
+  }
+}
+
+/// Escapes text so that it's displayed as is when inserted into markup
+/// (ex. a file name in a formatted message).
+///
+/// The text is also sanitized with `SanitizeMode::Remove` (see
+/// `ansi::sanitize`), since it may come from an untrusted source.
+pub fn escape(text: &str) -> Cow<'_, str> {
+  let text = ansi::sanitize(text, SanitizeMode::Remove);
+  if text.contains('[') {
+    Cow::Owned(text.replace('[', "[["))
+  } else {
+    text
+  }
+}
+
+/// The message of an error found at compile time by the `markup!` macro,
+/// which is written without allocating since `panic!` can only format
+/// strings in a const context.
+#[doc(hidden)]
+pub struct ConstErrorMessage {
+  bytes: [u8; 128],
+  len: usize,
+}
+
+impl ConstErrorMessage {
+  // the most bytes of the tag that are included in the message
+  const MAX_TAG_LEN: usize = 48;
+
+  /// Writes the message along with the byte index and the text of the
+  /// tag (ex. ``unknown style in tag at byte 6: `[bold blink]` ``).
+  pub const fn new(err: MarkupError, text: &str) -> Self {
+    let mut message = Self {
+      bytes: [0; 128],
+      len: 0,
+    };
+    message.push(err.kind.message().as_bytes());
+    message.push(b" at byte ");
+    message.push_number(err.index);
+    let bytes = text.as_bytes();
+    let mut start = if err.index < bytes.len() {
+      err.index
+    } else {
+      bytes.len()
+    };
+    while start > 0 && bytes[start] != b'[' {
+      start -= 1;
+    }
+    if start < bytes.len() && bytes[start] == b'[' {
+      let mut end = tag_end(bytes, start);
+      if end < bytes.len() {
+        end += 1;
+      }
+      if end - start > Self::MAX_TAG_LEN {
+        end = start + Self::MAX_TAG_LEN;
+        // keep whole chars
+        while end > start && (bytes[end] & 0xC0) == 0x80 {
+          end -= 1;
+        }
+      }
+      message.push(b": `");
+      message.push(subslice(bytes, start, end));
+      message.push(b"`");
+    }
+    message
+  }
+
+  pub const fn as_str(&self) -> &str {
+    match std::str::from_utf8(self.bytes.split_at(self.len).0) {
+      Ok(text) => text,
+      Err(_) => "invalid markup",
+    }
+  }
+
+  const fn push(&mut self, bytes: &[u8]) {
+    let mut i = 0;
+    while i < bytes.len() && self.len < self.bytes.len() {
+      self.bytes[self.len] = bytes[i];
+      self.len += 1;
+      i += 1;
+    }
+  }
+
+  const fn push_number(&mut self, mut value: usize) {
+    let mut digits = [0u8; 20];
+    let mut start = digits.len();
+    loop {
+      start -= 1;
+      digits[start] = b'0' + (value % 10) as u8;
+      value /= 10;
+      if value == 0 {
+        break;
+      }
+    }
+    self.push(subslice(&digits, start, digits.len()));
+  }
+}
+
+/// Parses markup in a string literal into a `StyledLine`, failing to
+/// compile when the markup is invalid (ex. a tag that's never closed).
+#[macro_export]
+macro_rules! markup {
+  ($text:literal) => {{
+    const _: () = if let Err(err) = $crate::markup::validate($text) {
+      let message = $crate::markup::ConstErrorMessage::new(err, $text);
+      panic!("{}", message.as_str())
+    };
+    match $crate::markup::parse($text) {
+      Ok(line) => line,
+      Err(err) => panic!("{}", err),
+    }
+  }};
+}
+
+// Gets the token at the index along with the index after it.
+const fn next_token(
+  bytes: &[u8],
+  index: usize,
+) -> Result<(Token, usize), MarkupError> {
+  if bytes[index] != b'[' {
+    let mut end = index + 1;
+    while end < bytes.len() && bytes[end] != b'[' {
+      end += 1;
+    }
+    return Ok((Token::Text(index, end), end));
+  }
+  if index + 1 < bytes.len() && bytes[index + 1] == b'[' {
+    // an escaped bracket is the first of the two
+    return Ok((Token::Text(index, index + 1), index + 2));
+  }
+  let end = tag_end(bytes, index);
+  if end == bytes.len() {
+    return Err(MarkupError {
+      kind: MarkupErrorKind::UnterminatedTag,
+      index,
+    });
+  }
+  if bytes[index + 1] == b'/' {
+    if end == index + 2 {
+      return Ok((Token::Close, end + 1));
+    }
+    return Err(MarkupError {
+      kind: MarkupErrorKind::InvalidClosingTag,
+      index,
+    });
+  }
+  match parse_tag(bytes, index + 1, end) {
+    Ok(token) => Ok((token, end + 1)),
+    Err(err) => Err(err),
+  }
+}
+
+// Gets the index of the `]` that ends the tag starting at the index,
+// skipping any within quotes, which is the length when there's none.
+const fn tag_end(bytes: &[u8], start: usize) -> usize {
+  let mut end = start + 1;
+  let mut is_quoted = false;
+  while end < bytes.len() && (is_quoted || bytes[end] != b']') {
+    if bytes[end] == b'"' {
+      is_quoted = !is_quoted;
+    }
+    end += 1;
+  }
+  end
+}
+
+// Parses the space separated words between the brackets of a tag.
+const fn parse_tag(
+  bytes: &[u8],
+  start: usize,
+  end: usize,
+) -> Result<Token, MarkupError> {
+  let mut style = Style::new();
+  let mut link = None;
+  let mut has_word = false;
+  let mut word_start = start;
+  while word_start < end {
+    if bytes[word_start] == b' ' {
+      word_start += 1;
+      continue;
+    }
+    let mut word_end = word_start;
+    while word_end < end && bytes[word_end] != b' ' {
+      word_end += 1;
+    }
+    has_word = true;
+    let word = subslice(bytes, word_start, word_end);
+    let error = MarkupError {
+      kind: MarkupErrorKind::UnknownStyle,
+      index: word_start,
+    };
+    if starts_with(word, b"link=") {
+      // the url is the rest of the tag, since it may contain spaces
+      let url = match parse_url(bytes, word_start + 5, end) {
+        Ok(url) => url,
+        Err(err) => return Err(err),
+      };
+      link = Some(url);
+      break;
+    } else if starts_with(word, b"on_") {
+      match parse_color(subslice(word, 3, word.len())) {
+        Some(color) => style = style.bg(color),
+        None => return Err(error),
+      }
+    } else if eq(word, b"bold") {
+      style = style.bold();
+    } else if eq(word, b"dim") {
+      style = style.dim();
+    } else if eq(word, b"italic") {
+      style = style.italic();
+    } else if eq(word, b"underline") {
+      style = style.underline();
+    } else if eq(word, b"inverse") {
+      style = style.inverse();
+    } else {
+      match parse_color(word) {
+        Some(color) => style = style.fg(color),
+        None => return Err(error),
+      }
+    }
+    word_start = word_end;
+  }
+  if !has_word {
+    return Err(MarkupError {
+      kind: MarkupErrorKind::UnknownStyle,
+      index: start,
+    });
+  }
+  Ok(Token::Open { style, link })
+}
+
+// Gets the range of the url in a link, which is quoted when it contains
+// brackets. An unquoted url with a `[` had its `]` taken as the end of the
+// tag (ex. `link=http://[::1]/`), so it's an error.
+const fn parse_url(
+  bytes: &[u8],
+  start: usize,
+  end: usize,
+) -> Result<(usize, usize), MarkupError> {
+  let error = MarkupError {
+    kind: MarkupErrorKind::InvalidLink,
+    index: start,
+  };
+  if start < end && bytes[start] == b'"' {
+    return if end - start >= 2 && bytes[end - 1] == b'"' {
+      Ok((start + 1, end - 1))
+    } else {
+      Err(error)
+    };
+  }
+  let mut i = start;
+  while i < end {
+    if bytes[i] == b'[' {
+      return Err(error);
+    }
+    i += 1;
+  }
+  Ok((start, end))
+}
+
+const fn parse_color(word: &[u8]) -> Option<Color> {
+  if let [b'#', hex @ ..] = word {
+    if hex.len() != 6 {
+      return None;
+    }
+    let mut rgb = [0u8; 3];
+    let mut i = 0;
+    while i < 6 {
+      let digit = match hex[i] {
+        b @ b'0'..=b'9' => b - b'0',
+        b @ b'a'..=b'f' => b - b'a' + 10,
+        b @ b'A'..=b'F' => b - b'A' + 10,
+        _ => return None,
+      };
+      rgb[i / 2] = rgb[i / 2] * 16 + digit;
+      i += 1;
+    }
+    return Some(Color::Rgb(rgb[0], rgb[1], rgb[2]));
+  }
+  if !word.is_empty() && word.len() <= 3 && word[0].is_ascii_digit() {
+    let mut index: u16 = 0;
+    let mut i = 0;
+    while i < word.len() {
+      if !word[i].is_ascii_digit() {
+        return None;
+      }
+      index = index * 10 + (word[i] - b'0') as u16;
+      i += 1;
+    }
+    return if index <= 255 {
+      Some(Color::Indexed(index as u8))
+    } else {
+      None
+    };
+  }
+  let (name, offset) = if starts_with(word, b"bright_") {
+    (subslice(word, 7, word.len()), 8)
+  } else {
+    (word, 0)
+  };
+  let mut i = 0;
+  while i < COLOR_NAMES.len() {
+    if eq(name, COLOR_NAMES[i].as_bytes()) {
+      return Some(Color::Ansi(offset + i as u8));
+    }
+    i += 1;
+  }
+  None
+}
+
+const fn subslice(bytes: &[u8], start: usize, end: usize) -> &[u8] {
+  bytes.split_at(end).0.split_at(start).1
+}
+
+const fn starts_with(bytes: &[u8], prefix: &[u8]) -> bool {
+  bytes.len() >= prefix.len() && eq(subslice(bytes, 0, prefix.len()), prefix)
+}
+
+const fn eq(a: &[u8], b: &[u8]) -> bool {
+  if a.len() != b.len() {
+    return false;
+  }
+  let mut i = 0;
+  while i < a.len() {
+    if a[i] != b[i] {
+      return false;
+    }
+    i += 1;
+  }
+  true
+}
+
+#[cfg(test)]
+mod test {
+  use pretty_assertions::assert_eq;
+
+  use super::ConstErrorMessage;
+  use super::MarkupError;
+  use super::MarkupErrorKind;
+  use super::escape;
+  use super::parse;
+  use super::validate;
+  use crate::Span;
+  use crate::Style;
+  use crate::ansi::Color;
+
+  #[test]
+  fn parses_markup() {
+    let line = parse("[bold green]✔[/] Finished [dim]a[[1][/]").unwrap();
+    assert_eq!(
+      line.spans,
+      vec![
+        Span::new("✔").with_style(Style::new().bold().fg(Color::Ansi(2))),
+        Span::new(" Finished "),
+        Span::new("a").with_style(Style::new().dim()),
+        Span::new("[").with_style(Style::new().dim()),
+        Span::new("1]").with_style(Style::new().dim()),
+      ]
+    );
+    let line = parse(
+      "[on_bright_red #ff8700][link=https://x.dev]a[italic 208]b[/][/]c[/]",
+    )
+    .unwrap();
+    let style = Style::new()
+      .bg(Color::Ansi(9))
+      .fg(Color::Rgb(0xff, 0x87, 0x00));
+    assert_eq!(
+      line.spans,
+      vec![
+        Span::new("a").with_style(style).with_link("https://x.dev"),
+        Span::new("b")
+          .with_style(style.italic().fg(Color::Indexed(208)))
+          .with_link("https://x.dev"),
+        Span::new("c").with_style(style),
+      ]
+    );
+    let line = parse("[link=\"http://[::1]:80/\"]x[/]").unwrap();
+    assert_eq!(
+      line.spans,
+      vec![Span::new("x").with_link("http://[::1]:80/")]
+    );
+    let line = crate::markup!("[bold]done[/]");
+    assert_eq!(line.to_string(), "\x1b[1mdone\x1b[0m");
+    assert_eq!(escape("a[b]"), "a[[b]");
+    assert_eq!(parse(&escape("a[b]")).unwrap().to_plain_string(), "a[b]");
+    assert_eq!(escape("a\x1b[2Kb\x07[c"), "ab^G[[c");
+  }
+
+  #[test]
+  fn errors_for_invalid_markup() {
+    let error = |kind, index| Err(MarkupError { kind, index });
+    for (text, expected) in [
+      ("[bold]a", error(MarkupErrorKind::UnclosedTag, 0)),
+      ("a[bold][dim]b[/]", error(MarkupErrorKind::UnclosedTag, 1)),
+      ("a[/]", error(MarkupErrorKind::UnexpectedClosingTag, 1)),
+      ("[bold a", error(MarkupErrorKind::UnterminatedTag, 0)),
+      ("[bold blink]a[/]", error(MarkupErrorKind::UnknownStyle, 6)),
+      ("[]a[/]", error(MarkupErrorKind::UnknownStyle, 1)),
+      ("[#ff]a[/]", error(MarkupErrorKind::UnknownStyle, 1)),
+      ("[256]a[/]", error(MarkupErrorKind::UnknownStyle, 1)),
+      (
+        "[bold]a[/bold]",
+        error(MarkupErrorKind::InvalidClosingTag, 7),
+      ),
+      ("[bold]a[/", error(MarkupErrorKind::UnterminatedTag, 7)),
+      (
+        "[link=http://[::1]:80/]x[/]",
+        error(MarkupErrorKind::InvalidLink, 6),
+      ),
+      (
+        "[link=\"http://x\" bold]x[/]",
+        error(MarkupErrorKind::InvalidLink, 6),
+      ),
+      (
+        "[link=\"http://x]x[/]",
+        error(MarkupErrorKind::UnterminatedTag, 0),
+      ),
+    ] {
+      assert_eq!(validate(text), expected, "{}", text);
+      assert_eq!(parse(text).map(|_| ()), expected, "{}", text);
+    }
+    assert_eq!(
+      parse("[bold]a").unwrap_err().to_string(),
+      "tag is never closed at byte 0"
+    );
+    for (text, expected) in [
+      (
+        "[bold blink]a[/]",
+        "unknown style in tag at byte 6: `[bold blink]`",
+      ),
+      ("a[/]", "closing tag without an open tag at byte 1: `[/]`"),
+      (
+        "[link=\"[::1]\" x]a[/]",
+        "link url must end the tag and be quoted when it has brackets at byte 6: `[link=\"[::1]\" x]`",
+      ),
+      (
+        "[bold a",
+        "tag is missing its closing bracket at byte 0: `[bold a`",
+      ),
+    ] {
+      let err = validate(text).unwrap_err();
+      assert_eq!(ConstErrorMessage::new(err, text).as_str(), expected);
+    }
+  }
+}
@@ -0,0 +1,143 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::WidthProfile;
+use crate::ansi;
+
+const BACKSPACE: &str = "\x08";
+
+// A column of a line, along with the escape sequences that were written
+// before its character.
+struct Cell<'a> {
+  escapes: String,
+  // empty for the columns after the first one of a wide character
+  text: &'a str,
+}
+
+/// Gets the text as a terminal would display it when `\r` moves back
+/// to the start of the line, so the text after it overwrites the text
+/// before it, and `\b` erases the previous character.
+pub fn apply_overwrites(text: &str, width_profile: WidthProfile) -> String {
+  let mut output = String::with_capacity(text.len());
+  for (i, line) in text.split('\n').enumerate() {
+    if i > 0 {
+      output.push('\n');
+    }
+    apply_line_overwrites(line, width_profile, &mut output);
+  }
+  output
+}
+
+fn apply_line_overwrites(
+  line: &str,
+  width_profile: WidthProfile,
+  output: &mut String,
+) {
+  let mut cells: Vec<Cell> = Vec::new();
+  let mut column = 0;
+  let mut escapes = String::new();
+  for token in ansi::tokenize(line) {
+    let chunk = &line[token.range];
+    if chunk == BACKSPACE {
+      if column > 0 {
+        let end = column;
+        column = char_start(&cells, column - 1);
+        if end == cells.len() {
+          for cell in cells.drain(column..).rev() {
+            escapes.insert_str(0, &cell.escapes);
+          }
+        } else {
+          blank_columns(&mut cells[column..end]);
+        }
+      }
+    } else if token.is_escape {
+      escapes.push_str(chunk);
+    } else {
+      for grapheme in chunk.graphemes(true) {
+        if grapheme == "\r" {
+          column = 0;
+          continue;
+        }
+        let width = width_profile.grapheme_width(grapheme).max(1);
+        // the rest of a wide character that's partly overwritten is blank
+        let start = char_start(&cells, column);
+        blank_columns(&mut cells[start..column]);
+        let end = column + width;
+        let next_end = char_end(&cells, end);
+        if end < next_end {
+          blank_columns(&mut cells[end..next_end]);
+        }
+        for i in column..end {
+          let text = if i == column { grapheme } else { "" };
+          match cells.get_mut(i) {
+            Some(cell) => cell.text = text,
+            None => cells.push(Cell {
+              escapes: String::new(),
+              text,
+            }),
+          }
+        }
+        cells[column]
+          .escapes
+          .push_str(&std::mem::take(&mut escapes));
+        column = end;
+      }
+    }
+  }
+  for cell in cells {
+    output.push_str(&cell.escapes);
+    output.push_str(cell.text);
+  }
+  output.push_str(&escapes);
+}
+
+// Gets the column that the character displayed at the column starts at.
+fn char_start(cells: &[Cell], mut column: usize) -> usize {
+  while column > 0 && cells.get(column).is_some_and(|c| c.text.is_empty()) {
+    column -= 1;
+  }
+  column
+}
+
+// Gets the column after the wide character continuing at the column.
+fn char_end(cells: &[Cell], mut column: usize) -> usize {
+  while cells.get(column).is_some_and(|c| c.text.is_empty()) {
+    column += 1;
+  }
+  column
+}
+
+fn blank_columns(cells: &mut [Cell]) {
+  for cell in cells {
+    cell.text = " ";
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use pretty_assertions::assert_eq;
+
+  use super::apply_overwrites;
+  use crate::WidthProfile;
+
+  #[test]
+  fn applies_overwrites() {
+    let apply_overwrites =
+      |text: &str| apply_overwrites(text, WidthProfile::Narrow);
+    assert_eq!(apply_overwrites("10%\r50%\r100%"), "100%");
+    assert_eq!(apply_overwrites("downloading\rdone"), "doneloading");
+    assert_eq!(apply_overwrites("abc\x08\x08d"), "ad");
+    assert_eq!(apply_overwrites("abc\r\x08\x08x"), "xbc");
+    assert_eq!(apply_overwrites("ab\rc\x08"), " b");
+    assert_eq!(apply_overwrites("a\r\nb\r\n"), "a\nb\n");
+    assert_eq!(
+      apply_overwrites("\x1b[31m1%\x1b[0m\r\x1b[32m2%\x1b[0m"),
+      "\x1b[31m\x1b[0m\x1b[32m2%\x1b[0m"
+    );
+    // wide characters are overwritten by display column
+    assert_eq!(apply_overwrites("ab\r你"), "你");
+    assert_eq!(apply_overwrites("你好\rx"), "x 好");
+    assert_eq!(apply_overwrites("你好\r你\x08"), "  好");
+    assert_eq!(apply_overwrites("你好\x08x"), "你x");
+    assert_eq!(apply_overwrites("a你b\x08\x08x"), "ax");
+  }
+}
@@ -0,0 +1,192 @@
+/// The graphic rendition (colors and text attributes) that's active
+/// after a sequence of SGR (`ESC [ ... m`) escape sequences.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SgrState {
+  bold: bool,
+  dim: bool,
+  italic: bool,
+  underline: Option<String>,
+  blink: Option<&'static str>,
+  inverse: bool,
+  hidden: bool,
+  strikethrough: bool,
+  overline: bool,
+  foreground: Option<String>,
+  background: Option<String>,
+  underline_color: Option<String>,
+}
+
+impl SgrState {
+  pub fn is_default(&self) -> bool {
+    *self == Self::default()
+  }
+
+  /// Updates the state with every SGR escape sequence in the text.
+  pub fn apply_text(&mut self, text: &str) {
+    if !text.as_bytes().contains(&0x1b) {
+      return;
+    }
+    for token in crate::ansi::tokenize(text) {
+      if token.is_escape {
+        self.apply_escape(&text[token.range]);
+      }
+    }
+  }
+
+  /// Updates the state with the escape sequence when it's an SGR sequence.
+  pub fn apply_escape(&mut self, escape: &str) {
+    if let Some(params) = sgr_params(escape) {
+      self.apply_params(params);
+    }
+  }
+
+  fn apply_params(&mut self, params: &str) {
+    let mut parts = params.split(';');
+    while let Some(part) = parts.next() {
+      let code = part.split(':').next().unwrap_or("");
+      match code {
+        "" | "0" => *self = Self::default(),
+        "1" => self.bold = true,
+        "2" => self.dim = true,
+        "3" => self.italic = true,
+        "4" if part == "4:0" => self.underline = None,
+        "4" | "21" => self.underline = Some(part.to_string()),
+        "5" => self.blink = Some("5"),
+        "6" => self.blink = Some("6"),
+        "7" => self.inverse = true,
+        "8" => self.hidden = true,
+        "9" => self.strikethrough = true,
+        "22" => {
+          self.bold = false;
+          self.dim = false;
+        }
+        "23" => self.italic = false,
+        "24" => self.underline = None,
+        "25" => self.blink = None,
+        "27" => self.inverse = false,
+        "28" => self.hidden = false,
+        "29" => self.strikethrough = false,
+        "30" | "31" | "32" | "33" | "34" | "35" | "36" | "37" | "90" | "91"
+        | "92" | "93" | "94" | "95" | "96" | "97" => {
+          self.foreground = Some(part.to_string());
+        }
+        "38" => self.foreground = extended_color(part, &mut parts),
+        "39" => self.foreground = None,
+        "40" | "41" | "42" | "43" | "44" | "45" | "46" | "47" | "100"
+        | "101" | "102" | "103" | "104" | "105" | "106" | "107" => {
+          self.background = Some(part.to_string());
+        }
+        "48" => self.background = extended_color(part, &mut parts),
+        "49" => self.background = None,
+        "53" => self.overline = true,
+        "55" => self.overline = false,
+        "58" => self.underline_color = extended_color(part, &mut parts),
+        "59" => self.underline_color = None,
+        _ => {}
+      }
+    }
+  }
+
+  /// Gets an escape sequence that sets this state from the default state.
+  pub fn to_escape(&self) -> String {
+    let mut params: Vec<&str> = Vec::new();
+    if self.bold {
+      params.push("1");
+    }
+    if self.dim {
+      params.push("2");
+    }
+    if self.italic {
+      params.push("3");
+    }
+    if let Some(underline) = &self.underline {
+      params.push(underline);
+    }
+    if let Some(blink) = self.blink {
+      params.push(blink);
+    }
+    if self.inverse {
+      params.push("7");
+    }
+    if self.hidden {
+      params.push("8");
+    }
+    if self.strikethrough {
+      params.push("9");
+    }
+    if self.overline {
+      params.push("53");
+    }
+    for color in [&self.foreground, &self.background, &self.underline_color]
+      .into_iter()
+      .flatten()
+    {
+      params.push(color);
+    }
+    if params.is_empty() {
+      String::new()
+    } else {
+      format!("\x1b[{}m", params.join(";"))
+    }
+  }
+}
+
+/// Gets the parameters of an SGR escape sequence (ex. `1;31` for `ESC[1;31m`).
+pub fn sgr_params(escape: &str) -> Option<&str> {
+  let params = escape
+    .strip_prefix("\x1b[")
+    .or_else(|| escape.strip_prefix('\u{9b}'))?
+    .strip_suffix('m')?;
+  if params
+    .bytes()
+    .all(|b| b.is_ascii_digit() || b == b';' || b == b':')
+  {
+    Some(params)
+  } else {
+    None
+  }
+}
+
+/// Reads an extended color (ex. `38;5;208` or `38;2;255;0;0`), which is
+/// either contained in the part using colons or in the following parts.
+fn extended_color<'a>(
+  part: &str,
+  parts: &mut impl Iterator<Item = &'a str>,
+) -> Option<String> {
+  if part.contains(':') {
+    return Some(part.to_string());
+  }
+  let mut color = part.to_string();
+  let kind = parts.next()?;
+  let count = match kind {
+    "5" => 1,
+    "2" => 3,
+    _ => return None,
+  };
+  color.push(';');
+  color.push_str(kind);
+  for _ in 0..count {
+    color.push(';');
+    color.push_str(parts.next()?);
+  }
+  Some(color)
+}
+
+#[cfg(test)]
+mod test {
+  use super::SgrState;
+
+  #[test]
+  fn tracks_sgr_state() {
+    let mut state = SgrState::default();
+    assert_eq!(state.to_escape(), "");
+    state.apply_text("\x1b[1;31mbold red\x1b[4m\x1b[2K");
+    assert_eq!(state.to_escape(), "\x1b[1;4;31m");
+    state.apply_text("\x1b[22;38;2;1;2;3;48;5;208m");
+    assert_eq!(state.to_escape(), "\x1b[4;38;2;1;2;3;48;5;208m");
+    state.apply_text("\x1b[24;39m");
+    assert_eq!(state.to_escape(), "\x1b[48;5;208m");
+    state.apply_text("\x1b[m");
+    assert!(state.is_default());
+  }
+}
@@ -0,0 +1,300 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+
+use crate::TextItem;
+use crate::ansi::Color;
+
+/// Colors and text attributes applied to a span of text.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+  foreground: Option<Color>,
+  background: Option<Color>,
+  bold: bool,
+  dim: bool,
+  italic: bool,
+  underline: bool,
+  inverse: bool,
+}
+
+impl Style {
+  pub const fn new() -> Self {
+    Self {
+      foreground: None,
+      background: None,
+      bold: false,
+      dim: false,
+      italic: false,
+      underline: false,
+      inverse: false,
+    }
+  }
+
+  pub const fn fg(mut self, color: Color) -> Self {
+    self.foreground = Some(color);
+    self
+  }
+
+  pub const fn bg(mut self, color: Color) -> Self {
+    self.background = Some(color);
+    self
+  }
+
+  pub const fn bold(mut self) -> Self {
+    self.bold = true;
+    self
+  }
+
+  pub const fn dim(mut self) -> Self {
+    self.dim = true;
+    self
+  }
+
+  pub const fn italic(mut self) -> Self {
+    self.italic = true;
+    self
+  }
+
+  pub const fn underline(mut self) -> Self {
+    self.underline = true;
+    self
+  }
+
+  pub const fn inverse(mut self) -> Self {
+    self.inverse = true;
+    self
+  }
+
+  /// Gets the style with the colors and attributes of the other style
+  /// applied on top of this one.
+  pub fn patch(self, other: Style) -> Self {
+    Self {
+      foreground: other.foreground.or(self.foreground),
+      background: other.background.or(self.background),
+      bold: self.bold || other.bold,
+      dim: self.dim || other.dim,
+      italic: self.italic || other.italic,
+      underline: self.underline || other.underline,
+      inverse: self.inverse || other.inverse,
+    }
+  }
+
+  /// Gets whether the style doesn't change how text is displayed.
+  pub fn is_plain(&self) -> bool {
+    *self == Self::default()
+  }
+
+  /// Gets the SGR escape sequence that sets the style from the default
+  /// style (ex. `ESC[1;32m`), which is empty for a plain style.
+  pub fn to_escape(&self) -> String {
+    let mut params = Vec::new();
+    for (is_set, param) in [
+      (self.bold, "1"),
+      (self.dim, "2"),
+      (self.italic, "3"),
+      (self.underline, "4"),
+      (self.inverse, "7"),
+    ] {
+      if is_set {
+        params.push(param.to_string());
+      }
+    }
+    if let Some(color) = self.foreground {
+      params.push(color_params(color, 30));
+    }
+    if let Some(color) = self.background {
+      params.push(color_params(color, 40));
+    }
+    if params.is_empty() {
+      String::new()
+    } else {
+      format!("\x1b[{}m", params.join(";"))
+    }
+  }
+}
+
+// Gets the SGR parameters for a foreground (base 30) or background
+// (base 40) color.
+fn color_params(color: Color, base: u8) -> String {
+  match color {
+    Color::Ansi(index @ 0..8) => (base + index).to_string(),
+    Color::Ansi(index) => (base + 60 + (index & 7)).to_string(),
+    Color::Indexed(index) => format!("{};5;{}", base + 8, index),
+    Color::Rgb(r, g, b) => format!("{};2;{};{};{}", base + 8, r, g, b),
+  }
+}
+
+/// Text displayed with a style, which may also be a hyperlink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span<'a> {
+  pub text: Cow<'a, str>,
+  pub style: Style,
+  pub link: Option<Cow<'a, str>>,
+}
+
+impl<'a> Span<'a> {
+  pub fn new(text: &'a str) -> Self {
+    Self {
+      text: Cow::Borrowed(text),
+      style: Style::default(),
+      link: None,
+    }
+  }
+
+  pub fn new_owned(text: String) -> Self {
+    Self {
+      text: Cow::Owned(text),
+      style: Style::default(),
+      link: None,
+    }
+  }
+
+  pub fn with_style(mut self, style: Style) -> Self {
+    self.style = style;
+    self
+  }
+
+  /// Makes the text a hyperlink to the url using an OSC 8 escape sequence.
+  ///
+  /// Control characters in the url are percent-encoded when displayed so
+  /// they can't end the escape sequence early.
+  pub fn with_link(mut self, url: &'a str) -> Self {
+    self.link = Some(Cow::Borrowed(url));
+    self
+  }
+
+  pub fn with_link_owned(mut self, url: String) -> Self {
+    self.link = Some(Cow::Owned(url));
+    self
+  }
+}
+
+/// Text made of styled spans, which is displayed with ANSI escape
+/// sequences.
+///
+/// The style of a span is restored on each line when it wraps and the
+/// styles are removed when colors are disabled (see
+/// `ConsoleStaticText::color`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StyledLine<'a> {
+  pub spans: Vec<Span<'a>>,
+}
+
+impl<'a> StyledLine<'a> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn push(&mut self, span: Span<'a>) {
+    self.spans.push(span);
+  }
+
+  pub fn with_span(mut self, span: Span<'a>) -> Self {
+    self.push(span);
+    self
+  }
+
+  /// Gets the text of the spans without any escape sequences.
+  pub fn to_plain_string(&self) -> String {
+    self.spans.iter().map(|span| span.text.as_ref()).collect()
+  }
+}
+
+impl<'a> From<Vec<Span<'a>>> for StyledLine<'a> {
+  fn from(spans: Vec<Span<'a>>) -> Self {
+    Self { spans }
+  }
+}
+
+impl std::fmt::Display for StyledLine<'_> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    for span in &self.spans {
+      if let Some(link) = &span.link {
+        f.write_str("\x1b]8;;")?;
+        write_link_url(f, link)?;
+        f.write_str("\x1b\\")?;
+      }
+      let escape = span.style.to_escape();
+      f.write_str(&escape)?;
+      f.write_str(&span.text)?;
+      if !escape.is_empty() {
+        f.write_str("\x1b[0m")?;
+      }
+      if span.link.is_some() {
+        f.write_str("\x1b]8;;\x1b\\")?;
+      }
+    }
+    Ok(())
+  }
+}
+
+// Writes the url with its control characters percent-encoded, since BEL,
+// ESC, or a C1 control would end the OSC 8 sequence and let the rest of
+// the url run as escape sequences.
+fn write_link_url(
+  f: &mut std::fmt::Formatter<'_>,
+  url: &str,
+) -> std::fmt::Result {
+  for (index, c) in url.char_indices() {
+    if c.is_control() {
+      for byte in &url.as_bytes()[index..index + c.len_utf8()] {
+        write!(f, "%{:02X}", byte)?;
+      }
+    } else {
+      f.write_char(c)?;
+    }
+  }
+  Ok(())
+}
+
+impl<'a> From<StyledLine<'_>> for TextItem<'a> {
+  fn from(line: StyledLine<'_>) -> Self {
+    let mut text = String::new();
+    // writing to a string never fails
+    let _ = write!(text, "{}", line);
+    TextItem::new_owned(text)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use pretty_assertions::assert_eq;
+
+  use super::Span;
+  use super::Style;
+  use super::StyledLine;
+  use crate::ansi::Color;
+
+  #[test]
+  fn formats_styled_lines() {
+    let style = Style::new().bold().fg(Color::Ansi(2));
+    assert_eq!(style.to_escape(), "\x1b[1;32m");
+    assert_eq!(
+      Style::new()
+        .underline()
+        .fg(Color::Ansi(9))
+        .bg(Color::Rgb(1, 2, 3))
+        .to_escape(),
+      "\x1b[4;91;48;2;1;2;3m"
+    );
+    assert_eq!(
+      style.patch(Style::new().dim().fg(Color::Indexed(208))),
+      Style::new().bold().dim().fg(Color::Indexed(208))
+    );
+    let line = StyledLine::new()
+      .with_span(Span::new("✔").with_style(style))
+      .with_span(Span::new(" see "))
+      .with_span(Span::new("docs").with_link("https://x.dev"));
+    assert_eq!(
+      line.to_string(),
+      "\x1b[1;32m✔\x1b[0m see \x1b]8;;https://x.dev\x1b\\docs\x1b]8;;\x1b\\"
+    );
+    assert_eq!(line.to_plain_string(), "✔ see docs");
+    // control characters can't end the hyperlink early
+    let line = StyledLine::new()
+      .with_span(Span::new("x").with_link("http://a\x07\x1b[2J\u{9c}"));
+    assert_eq!(
+      line.to_string(),
+      "\x1b]8;;http://a%07%1B[2J%C2%9C\x1b\\x\x1b]8;;\x1b\\"
+    );
+  }
+}
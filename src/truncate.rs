@@ -0,0 +1,174 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::LinePrefix;
+use crate::PendingLine;
+use crate::WidthProfile;
+use crate::ansi;
+use crate::ansi::ELLIPSIS;
+use crate::tab_stop_width;
+
+/// Where the ellipsis goes when truncating text.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Ellipsis {
+  /// Keeps the end of the text (ex. `…ng text`).
+  Start,
+  /// Keeps the start and end of the text (ex. `lon…ext`).
+  Middle,
+  /// Keeps the start of the text (ex. `long te…`).
+  #[default]
+  End,
+}
+
+// A piece of a line of text, which is either a zero-width escape
+// sequence or a visible grapheme cluster (or tab) with its width.
+enum Cell<'a> {
+  Escape(&'a str),
+  Visible(&'a str, usize),
+  Tab(usize),
+}
+
+impl Cell<'_> {
+  fn width(&self) -> usize {
+    match self {
+      Cell::Escape(_) => 0,
+      Cell::Visible(_, width) | Cell::Tab(width) => *width,
+    }
+  }
+}
+
+/// Lays out the text on a single line of at most `terminal_width` columns,
+/// replacing what doesn't fit with an ellipsis (or nothing when `None`).
+///
+/// Escape sequences in the removed text are kept so that styles opened
+/// or closed there still apply after the ellipsis.
+pub fn truncate_line<'a>(
+  text: &'a str,
+  prefix: LinePrefix<'a>,
+  terminal_width: usize,
+  ellipsis: Option<Ellipsis>,
+  tab_width: usize,
+  width_profile: WidthProfile,
+) -> PendingLine<'a> {
+  let cells = get_cells(text, prefix.width, tab_width, width_profile);
+  let mut line = PendingLine::new(prefix);
+  let max_width = terminal_width.saturating_sub(prefix.width);
+  let total_width = cells.iter().map(|c| c.width()).sum::<usize>();
+  if total_width <= max_width {
+    push_cells(&mut line, &cells);
+    return line;
+  }
+
+  let Some(ellipsis) = ellipsis else {
+    let end = take_width_from_start(&cells, max_width);
+    push_cells(&mut line, &cells[..end]);
+    push_escapes(&mut line, &cells[end..]);
+    return line;
+  };
+
+  let ellipsis_width = width_profile.str_width(ELLIPSIS);
+  if ellipsis_width > max_width {
+    push_escapes(&mut line, &cells);
+    return line;
+  }
+  let budget = max_width - ellipsis_width;
+  match ellipsis {
+    Ellipsis::Start => {
+      let start = take_width_from_end(&cells, budget);
+      line.push_segment(ELLIPSIS, ellipsis_width);
+      push_escapes(&mut line, &cells[..start]);
+      push_cells(&mut line, &cells[start..]);
+    }
+    Ellipsis::Middle => {
+      let head_end = take_width_from_start(&cells, budget.div_ceil(2));
+      let head_width: usize = cells[..head_end].iter().map(|c| c.width()).sum();
+      let tail_start =
+        head_end + take_width_from_end(&cells[head_end..], budget - head_width);
+      push_cells(&mut line, &cells[..head_end]);
+      line.push_segment(ELLIPSIS, ellipsis_width);
+      push_escapes(&mut line, &cells[head_end..tail_start]);
+      push_cells(&mut line, &cells[tail_start..]);
+    }
+    Ellipsis::End => {
+      let end = take_width_from_start(&cells, budget);
+      push_cells(&mut line, &cells[..end]);
+      line.push_segment(ELLIPSIS, ellipsis_width);
+      push_escapes(&mut line, &cells[end..]);
+    }
+  }
+  line
+}
+
+fn get_cells<'a>(
+  text: &'a str,
+  start_column: usize,
+  tab_width: usize,
+  width_profile: WidthProfile,
+) -> Vec<Cell<'a>> {
+  let mut cells = Vec::new();
+  let mut column = start_column;
+  for token in ansi::tokenize(text) {
+    let chunk = &text[token.range];
+    if token.is_escape {
+      let width = ansi::movement_width(chunk, column);
+      if width > 0 {
+        cells.push(Cell::Visible(chunk, width));
+        column += width;
+      } else {
+        cells.push(Cell::Escape(chunk));
+      }
+      continue;
+    }
+    for grapheme in chunk.graphemes(true) {
+      let cell = if grapheme == "\t" {
+        Cell::Tab(tab_stop_width(column, tab_width))
+      } else {
+        Cell::Visible(grapheme, width_profile.grapheme_width(grapheme))
+      };
+      column += cell.width();
+      cells.push(cell);
+    }
+  }
+  cells
+}
+
+/// Gets the index of the first cell that doesn't fit in the width.
+fn take_width_from_start(cells: &[Cell], max_width: usize) -> usize {
+  let mut width = 0;
+  for (i, cell) in cells.iter().enumerate() {
+    width += cell.width();
+    if width > max_width {
+      return i;
+    }
+  }
+  cells.len()
+}
+
+/// Gets the index of the first cell of the end that fits in the width.
+fn take_width_from_end(cells: &[Cell], max_width: usize) -> usize {
+  let mut width = 0;
+  for (i, cell) in cells.iter().enumerate().rev() {
+    width += cell.width();
+    if width > max_width {
+      return i + 1;
+    }
+  }
+  0
+}
+
+fn push_cells<'a>(line: &mut PendingLine<'a>, cells: &[Cell<'a>]) {
+  for cell in cells {
+    match cell {
+      Cell::Escape(text) => line.push_segment(text, 0),
+      Cell::Visible(text, width) => line.push_segment(text, *width),
+      Cell::Tab(width) => line.push_spaces(*width),
+    }
+  }
+}
+
+fn push_escapes<'a>(line: &mut PendingLine<'a>, cells: &[Cell<'a>]) {
+  for cell in cells {
+    if let Cell::Escape(text) = cell {
+      line.push_segment(text, 0);
+    }
+  }
+}
@@ -0,0 +1,163 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
+
+use crate::ansi::strip_ansi_codes_with_profile;
+
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+const EMOJI_PRESENTATION_SELECTOR: char = '\u{FE0F}';
+
+/// How text is measured, which should match how the terminal displays it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WidthProfile {
+  /// Characters with an East Asian Width of "Ambiguous" (ex. box drawing
+  /// characters, `○`, `①`, and Greek letters) occupy a single column.
+  #[default]
+  Narrow,
+  /// Characters with an East Asian Width of "Ambiguous" occupy two
+  /// columns, which is how terminals configured for CJK display them.
+  Wide,
+}
+
+impl WidthProfile {
+  /// Detects the profile from the locale in the `LC_ALL`, `LC_CTYPE`,
+  /// and `LANG` environment variables, using `Wide` for Chinese,
+  /// Japanese, and Korean locales.
+  pub fn from_locale() -> Self {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+      .iter()
+      .filter_map(|name| std::env::var(name).ok())
+      .find(|value| !value.is_empty());
+    match locale {
+      Some(locale) if is_cjk_locale(&locale) => Self::Wide,
+      _ => Self::Narrow,
+    }
+  }
+
+  /// Gets the number of columns the provided text (which may contain
+  /// ANSI escape sequences) occupies in a terminal.
+  pub(crate) fn text_width(self, text: &str) -> usize {
+    self.str_width(&strip_ansi_codes_with_profile(text, self))
+  }
+
+  /// Gets the number of columns the provided text occupies in a terminal,
+  /// measuring each extended grapheme cluster as a single unit.
+  ///
+  /// The text must not contain ANSI escape sequences.
+  pub(crate) fn str_width(self, text: &str) -> usize {
+    if text.is_ascii() {
+      // fast path: every printable ascii char is a single grapheme of width 1
+      return text.bytes().filter(|b| !b.is_ascii_control()).count();
+    }
+    text.graphemes(true).map(|g| self.grapheme_width(g)).sum()
+  }
+
+  /// Gets the number of columns an extended grapheme cluster occupies.
+  ///
+  /// Terminals lay out a cluster based on its first code point, so joined
+  /// emoji sequences (ZWJ families, skin tones) and combining accents don't
+  /// add to the width the way summing each char would.
+  pub(crate) fn grapheme_width(self, grapheme: &str) -> usize {
+    let mut chars = grapheme.chars();
+    let Some(first) = chars.next() else {
+      return 0;
+    };
+    if is_regional_indicator(first) {
+      // flags are a pair of regional indicators drawn as one wide glyph
+      return 2;
+    }
+    let base_width = self.char_width(first).unwrap_or(0);
+    if base_width == 0 {
+      // a cluster with a zero width base (ex. a lone combining mark) is
+      // drawn as the sum of its parts
+      return grapheme
+        .chars()
+        .filter(|c| *c != ZERO_WIDTH_JOINER)
+        .map(|c| self.char_width(c).unwrap_or(0))
+        .sum();
+    }
+    if base_width == 1 && chars.any(|c| c == EMOJI_PRESENTATION_SELECTOR) {
+      // ex. "❤️" is a narrow char displayed as a wide emoji
+      return 2;
+    }
+    base_width
+  }
+
+  /// Gets the width of a single char, which is `None` for control chars.
+  pub(crate) fn char_width(self, c: char) -> Option<usize> {
+    match self {
+      Self::Narrow => c.width(),
+      Self::Wide if is_ambiguous_letter(c) => Some(2),
+      Self::Wide => c.width_cjk(),
+    }
+  }
+}
+
+/// Gets whether the char is a Greek or Cyrillic letter with an East Asian
+/// Width of "Ambiguous", which newer versions of `unicode-width` measure
+/// as narrow even in a CJK context.
+fn is_ambiguous_letter(c: char) -> bool {
+  matches!(
+    c,
+    '\u{0391}'..='\u{03A1}'
+      | '\u{03A3}'..='\u{03A9}'
+      | '\u{03B1}'..='\u{03C1}'
+      | '\u{03C3}'..='\u{03C9}'
+      | '\u{0401}'
+      | '\u{0410}'..='\u{044F}'
+      | '\u{0451}'
+  )
+}
+
+fn is_regional_indicator(c: char) -> bool {
+  ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
+
+fn is_cjk_locale(locale: &str) -> bool {
+  let language = locale.split(['_', '-', '.', '@']).next().unwrap_or("");
+  matches!(language, "zh" | "ja" | "ko")
+}
+
+#[cfg(test)]
+mod test {
+  use super::WidthProfile;
+  use super::is_cjk_locale;
+
+  #[test]
+  fn measures_grapheme_clusters() {
+    let profile = WidthProfile::Narrow;
+    assert_eq!(profile.str_width("abc"), 3);
+    // family: man, zwj, woman, zwj, girl
+    assert_eq!(
+      profile.str_width("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}"),
+      2
+    );
+    // flag of Canada
+    assert_eq!(profile.str_width("\u{1F1E8}\u{1F1E6}"), 2);
+    // e + combining acute accent
+    assert_eq!(profile.str_width("e\u{301}"), 1);
+    // heavy black heart with emoji presentation selector
+    assert_eq!(profile.str_width("\u{2764}\u{FE0F}"), 2);
+    assert_eq!(profile.str_width("\u{2764}"), 1);
+    assert_eq!(profile.text_width("\x1b[31m\u{1F1E8}\u{1F1E6}\x1b[0m!"), 3);
+  }
+
+  #[test]
+  fn measures_ambiguous_width() {
+    for text in ["○", "①", "─", "α", "Ж"] {
+      assert_eq!(WidthProfile::Narrow.str_width(text), 1, "{}", text);
+      assert_eq!(WidthProfile::Wide.str_width(text), 2, "{}", text);
+    }
+    // unambiguous text is the same in both
+    assert_eq!(WidthProfile::Wide.str_width("ab你"), 4);
+  }
+
+  #[test]
+  fn detects_cjk_locales() {
+    assert!(is_cjk_locale("ja_JP.UTF-8"));
+    assert!(is_cjk_locale("zh_TW"));
+    assert!(is_cjk_locale("ko"));
+    assert!(!is_cjk_locale("en_US.UTF-8"));
+    assert!(!is_cjk_locale("C"));
+    assert!(!is_cjk_locale(""));
+  }
+}
@@ -1,97 +1,202 @@
+use std::collections::VecDeque;
+
+use unicode_linebreak::linebreaks;
+
+use crate::ansi;
+
+const SOFT_HYPHEN: char = '\u{AD}';
+
 #[derive(PartialEq, Debug)]
 pub enum WordToken<'a> {
   Word(&'a str),
   WhiteSpace(&'a str),
+  /// A soft hyphen ending the previous word, which should only be
+  /// displayed (as a `-`) when the line is broken at it.
+  SoftHyphen,
   LfNewLine,
   CrlfNewLine,
 }
 
-impl<'a> WordToken<'a> {
-  pub fn len(&self) -> usize {
-    match self {
-      WordToken::Word(text) => text.len(),
-      WordToken::WhiteSpace(text) => text.len(),
-      WordToken::LfNewLine => 1,
-      WordToken::CrlfNewLine => 2,
-    }
-  }
-}
-
 /// Takes a string and tokenizes it into words, whitespace runs, and newlines.
+///
+/// Words end at the break opportunities of the Unicode line breaking
+/// algorithm (UAX #14), so a word may end in a hyphen, slash, or zero
+/// width space and text without spaces (ex. CJK) is split between the
+/// characters where a break is allowed. Non-breaking spaces are part of
+/// the surrounding word.
 pub fn tokenize_words(text: &str) -> impl Iterator<Item = WordToken<'_>> {
   TokenIterator {
     text,
-    current_index: 0,
+    breaks: break_indices(text),
+    segment_start: 0,
+    pending: VecDeque::new(),
   }
 }
 
-struct TokenIterator<'a> {
+struct TokenIterator<'a, I: Iterator<Item = usize>> {
   text: &'a str,
-  current_index: usize,
+  breaks: I,
+  segment_start: usize,
+  // the tokens of the last segment that haven't been returned yet
+  pending: VecDeque<WordToken<'a>>,
 }
 
-impl<'a> Iterator for TokenIterator<'a> {
+impl<'a, I: Iterator<Item = usize>> Iterator for TokenIterator<'a, I> {
   type Item = WordToken<'a>;
 
   fn next(&mut self) -> Option<Self::Item> {
-    let remaining_text = &self.text[self.current_index..];
-    if remaining_text.is_empty() {
-      return None;
-    }
-
-    let mut chars = remaining_text.char_indices();
-    let (_, first_char) = chars.next().unwrap();
-    let token = match first_char {
-      '\n' => WordToken::LfNewLine,
-      '\r'
-        if remaining_text.as_bytes().get(first_char.len_utf8())
-          == Some(&b'\n') =>
-      {
-        WordToken::CrlfNewLine
+    loop {
+      if let Some(token) = self.pending.pop_front() {
+        return Some(token);
       }
-      // a lone `\r` is treated as part of a word — match find_word_boundary below
-      c if c.is_whitespace() && c != '\r' => {
-        let end = find_whitespace_run_end(remaining_text);
-        WordToken::WhiteSpace(&remaining_text[..end])
+      let end = match self.breaks.next() {
+        Some(end) => end,
+        None if self.segment_start < self.text.len() => self.text.len(),
+        None => return None,
+      };
+      if end <= self.segment_start {
+        continue;
       }
-      _ => {
-        let end =
-          find_word_boundary(remaining_text).unwrap_or(remaining_text.len());
-        WordToken::Word(&remaining_text[..end])
+      let segment = &self.text[self.segment_start..end];
+      // a lone `\r` is a mandatory break in UAX #14, but treat
+      // it as part of a word here in order to keep it on the line
+      if segment.ends_with('\r') && end < self.text.len() {
+        continue;
       }
-    };
-    self.current_index += token.len();
-    Some(token)
+      push_segment_tokens(segment, &mut self.pending);
+      self.segment_start = end;
+    }
+  }
+}
+
+// The break opportunities of either text without escape sequences, which
+// are found lazily, or text with escape sequences.
+enum BreakIndices<P: Iterator<Item = usize>> {
+  Plain(P),
+  Mapped(std::vec::IntoIter<usize>),
+}
+
+impl<P: Iterator<Item = usize>> Iterator for BreakIndices<P> {
+  type Item = usize;
+
+  fn next(&mut self) -> Option<usize> {
+    match self {
+      BreakIndices::Plain(iter) => iter.next(),
+      BreakIndices::Mapped(iter) => iter.next(),
+    }
   }
 }
 
-fn find_whitespace_run_end(text: &str) -> usize {
-  for (index, c) in text.char_indices() {
-    // newlines and lone `\r` end the whitespace run
-    match c {
-      '\n' => return index,
-      '\r' => return index,
-      c if !c.is_whitespace() => return index,
-      _ => {}
+/// Gets the byte indexes in the text where a line may be broken.
+fn break_indices(text: &str) -> impl Iterator<Item = usize> + '_ {
+  if !text.as_bytes().contains(&0x1b) {
+    return BreakIndices::Plain(linebreaks(text).map(|(index, _)| index));
+  }
+  // find the break opportunities of the visible text so that escape
+  // sequences (ex. the url in an OSC 8 hyperlink) are never split, then
+  // map them back to the original text. Escape sequences before a break
+  // opportunity's visible char stay with that char.
+  let mut visible_text = String::with_capacity(text.len());
+  let mut original_indexes = Vec::with_capacity(text.len() + 1);
+  for token in ansi::tokenize(text) {
+    if !token.is_escape {
+      visible_text.push_str(&text[token.range.clone()]);
+      original_indexes.extend(token.range);
     }
   }
-  text.len()
+  original_indexes.push(text.len());
+  let indexes = linebreaks(&visible_text)
+    .map(|(index, _)| original_indexes[index])
+    .collect::<Vec<_>>();
+  BreakIndices::Mapped(indexes.into_iter())
 }
 
-fn find_word_boundary(text: &str) -> Option<usize> {
-  let mut chars = text.char_indices().peekable();
-  while let Some((index, c)) = chars.next() {
-    match c {
-      '\n' => return Some(index),
-      '\r' if chars.peek().map(|(_, c)| *c) == Some('\n') => {
-        return Some(index);
+/// Splits text between two break opportunities into its word,
+/// trailing whitespace, and newline.
+fn push_segment_tokens<'a>(
+  segment: &'a str,
+  tokens: &mut VecDeque<WordToken<'a>>,
+) {
+  let (segment, newline) = if let Some(text) = segment.strip_suffix("\r\n") {
+    (text, Some(WordToken::CrlfNewLine))
+  } else if let Some(text) = segment.strip_suffix('\n') {
+    (text, Some(WordToken::LfNewLine))
+  } else {
+    (segment, None)
+  };
+
+  if segment.as_bytes().contains(&0x1b) {
+    // escape sequences within the trailing whitespace become
+    // zero width words so they're never dropped at a line break
+    let ansi_tokens = ansi::tokenize(segment);
+    let mut word_end = 0;
+    for (i, token) in ansi_tokens.iter().enumerate().rev() {
+      if !token.is_escape {
+        let text = &segment[token.range.clone()];
+        let trimmed_len = text.trim_end_matches(is_breaking_whitespace).len();
+        if trimmed_len == text.len() {
+          // escape sequences directly after the word are part of it
+          word_end = ansi_tokens[i + 1..]
+            .iter()
+            .take_while(|t| t.is_escape)
+            .last()
+            .map_or(token.range.end, |t| t.range.end);
+          break;
+        } else if trimmed_len > 0 {
+          word_end = token.range.start + trimmed_len;
+          break;
+        }
+      }
+    }
+    push_word(&segment[..word_end], tokens);
+    for token in ansi_tokens {
+      if token.range.end <= word_end {
+        continue;
+      }
+      let text = &segment[token.range.start.max(word_end)..token.range.end];
+      if token.is_escape {
+        tokens.push_back(WordToken::Word(text));
+      } else {
+        tokens.push_back(WordToken::WhiteSpace(text));
+      }
+    }
+  } else {
+    let word_end = segment.trim_end_matches(is_breaking_whitespace).len();
+    push_word(&segment[..word_end], tokens);
+    if word_end < segment.len() {
+      tokens.push_back(WordToken::WhiteSpace(&segment[word_end..]));
+    }
+  }
+
+  if let Some(newline) = newline {
+    tokens.push_back(newline);
+  }
+}
+
+fn push_word<'a>(word: &'a str, tokens: &mut VecDeque<WordToken<'a>>) {
+  if word.is_empty() {
+    return;
+  }
+  if let Some(index) = word.rfind(SOFT_HYPHEN) {
+    let after = &word[index + SOFT_HYPHEN.len_utf8()..];
+    if ansi::strip_ansi_codes(after).is_empty() {
+      if index > 0 {
+        tokens.push_back(WordToken::Word(&word[..index]));
       }
-      '\r' => {} // don't bother with \r only newlines... skip
-      c if c.is_whitespace() => return Some(index),
-      _ => {}
+      tokens.push_back(WordToken::SoftHyphen);
+      if !after.is_empty() {
+        tokens.push_back(WordToken::Word(after));
+      }
+      return;
     }
   }
-  None
+  tokens.push_back(WordToken::Word(word));
+}
+
+fn is_breaking_whitespace(c: char) -> bool {
+  // newlines are handled separately and a lone `\r` is part of a word
+  c.is_whitespace()
+    && !matches!(c, '\u{A0}' | '\u{2007}' | '\u{202F}' | '\n' | '\r')
 }
 
 #[cfg(test)]
@@ -193,4 +298,79 @@ mod tokenize_tests {
     let result = tokenize_words("⌘");
     assert_eq!(result.collect::<Vec<_>>(), [WordToken::Word("⌘"),]);
   }
+
+  #[test]
+  fn tokenize_words_non_breaking_space() {
+    let result = tokenize_words("10\u{A0}MiB done");
+    assert_eq!(
+      result.collect::<Vec<_>>(),
+      [
+        WordToken::Word("10\u{A0}MiB"),
+        WordToken::WhiteSpace(" "),
+        WordToken::Word("done")
+      ]
+    );
+  }
+
+  #[test]
+  fn tokenize_words_break_opportunities() {
+    let result = tokenize_words("well-known a/b zero\u{200B}width");
+    assert_eq!(
+      result.collect::<Vec<_>>(),
+      [
+        WordToken::Word("well-"),
+        WordToken::Word("known"),
+        WordToken::WhiteSpace(" "),
+        WordToken::Word("a/"),
+        WordToken::Word("b"),
+        WordToken::WhiteSpace(" "),
+        WordToken::Word("zero\u{200B}"),
+        WordToken::Word("width"),
+      ]
+    );
+  }
+
+  #[test]
+  fn tokenize_words_cjk() {
+    let result = tokenize_words("你好世界");
+    assert_eq!(
+      result.collect::<Vec<_>>(),
+      [
+        WordToken::Word("你"),
+        WordToken::Word("好"),
+        WordToken::Word("世"),
+        WordToken::Word("界"),
+      ]
+    );
+  }
+
+  #[test]
+  fn tokenize_words_soft_hyphen() {
+    let result = tokenize_words("hy\u{AD}phen");
+    assert_eq!(
+      result.collect::<Vec<_>>(),
+      [
+        WordToken::Word("hy"),
+        WordToken::SoftHyphen,
+        WordToken::Word("phen"),
+      ]
+    );
+  }
+
+  #[test]
+  fn tokenize_words_escape_sequences() {
+    // the slash in the hyperlink's url is not a break opportunity
+    let text = "\x1b]8;;https://a/b\x1b\\link\x1b]8;;\x1b\\ \x1b[0m x";
+    let result = tokenize_words(text);
+    assert_eq!(
+      result.collect::<Vec<_>>(),
+      [
+        WordToken::Word("\x1b]8;;https://a/b\x1b\\link\x1b]8;;\x1b\\"),
+        WordToken::WhiteSpace(" "),
+        WordToken::Word("\x1b[0m"),
+        WordToken::WhiteSpace(" "),
+        WordToken::Word("x"),
+      ]
+    );
+  }
 }